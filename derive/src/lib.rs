@@ -0,0 +1,202 @@
+//! Derives for the `FromTerm` and `IntoTerm` traits of `erlang-cnode`, and the `term!` macro.
+//!
+//! A struct is converted to a record-style tuple whose first element is an atom naming the record,
+//! followed by the fields in declaration order. The record name defaults to the struct name in
+//! snake case and can be set with `#[term(record = "name")]`.
+
+extern crate proc_macro;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned};
+
+mod term;
+
+/// The largest atom, in Unicode code points, that the runtime accepts.
+const MAX_ATOM_LEN: usize = 255;
+
+#[proc_macro_derive(FromTerm, attributes(term))]
+pub fn derive_from_term(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as syn::DeriveInput);
+  expand_from_term(&input)
+    .unwrap_or_else(|error| error.to_compile_error())
+    .into()
+}
+
+#[proc_macro_derive(IntoTerm, attributes(term))]
+pub fn derive_into_term(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as syn::DeriveInput);
+  expand_into_term(&input)
+    .unwrap_or_else(|error| error.to_compile_error())
+    .into()
+}
+
+/// Builds a `Term` from Erlang literal syntax, such as `term!({ok, [1, 2 | T], <<"bin">>})`.
+#[proc_macro]
+pub fn term(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let term::TermLiteral(term) = parse_macro_input!(input as term::TermLiteral);
+  term.into()
+}
+
+fn expand_from_term(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let record = record_name(input)?;
+  let fields = struct_fields(input)?;
+  let field_count = fields.len();
+  let generics = add_bound(&input.generics, quote!(::erlang_cnode::FromTerm));
+  let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+  let conversions = fields.iter().enumerate().map(|(index, field)| {
+    let field_name = match &field.ident {
+      Some(ident) => ident.to_string(),
+      None => (index + 1).to_string(),
+    };
+    quote! {
+      ::erlang_cnode::read_record_field(&fields[#index], #record, #field_name)?
+    }
+  });
+  let construction = match fields {
+    syn::Fields::Named(_) => {
+      let idents = fields.iter().map(|field| &field.ident);
+      quote!(#name { #(#idents: #conversions,)* })
+    }
+    syn::Fields::Unnamed(_) => quote!(#name(#(#conversions,)*)),
+    syn::Fields::Unit => quote!(#name),
+  };
+
+  let binding = if field_count == 0 {
+    quote!(_)
+  } else {
+    quote!(fields)
+  };
+
+  Ok(quote! {
+    impl #impl_generics ::erlang_cnode::FromTerm for #name #ty_generics #where_clause {
+      fn from_term(term: &::erlang_cnode::Term) -> ::erlang_cnode::Result<Self> {
+        let #binding = ::erlang_cnode::read_record(term, #record, #field_count)?;
+        Ok(#construction)
+      }
+    }
+  })
+}
+
+fn expand_into_term(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+  let name = &input.ident;
+  let record = record_name(input)?;
+  let fields = struct_fields(input)?;
+  let generics = add_bound(&input.generics, quote!(::erlang_cnode::IntoTerm));
+  let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+  let conversions = fields.iter().enumerate().map(|(index, field)| {
+    let member = match &field.ident {
+      Some(ident) => syn::Member::Named(ident.clone()),
+      None => syn::Member::Unnamed(syn::Index {
+        index: index as u32,
+        span: field.span(),
+      }),
+    };
+    quote!(::erlang_cnode::IntoTerm::into_term(self.#member))
+  });
+
+  Ok(quote! {
+    impl #impl_generics ::erlang_cnode::IntoTerm for #name #ty_generics #where_clause {
+      fn into_term(self) -> ::erlang_cnode::Term {
+        ::erlang_cnode::write_record(#record, vec![#(#conversions),*])
+      }
+    }
+  })
+}
+
+fn struct_fields(input: &syn::DeriveInput) -> syn::Result<&syn::Fields> {
+  match &input.data {
+    syn::Data::Struct(data) => Ok(&data.fields),
+    _ => Err(syn::Error::new(
+      Span::call_site(),
+      "only structs can be converted to and from records",
+    )),
+  }
+}
+
+fn record_name(input: &syn::DeriveInput) -> syn::Result<String> {
+  let mut record = None;
+  for attr in &input.attrs {
+    if !attr.path.is_ident("term") {
+      continue;
+    }
+    match attr.parse_meta()? {
+      syn::Meta::List(list) => {
+        for nested in &list.nested {
+          match nested {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+              path,
+              lit: syn::Lit::Str(value),
+              ..
+            }))
+              if path.is_ident("record") =>
+            {
+              record = Some((value.value(), value.span()))
+            }
+            _ => {
+              return Err(syn::Error::new_spanned(
+                nested,
+                "expected `record = \"...\"`",
+              ))
+            }
+          }
+        }
+      }
+      meta => {
+        return Err(syn::Error::new_spanned(
+          meta,
+          "expected `term(record = \"...\")`",
+        ))
+      }
+    }
+  }
+
+  let (record, span) =
+    record.unwrap_or_else(|| (snake_case(&input.ident.to_string()), input.ident.span()));
+  let record_len = record.chars().count();
+  if record_len > MAX_ATOM_LEN {
+    return Err(syn::Error::new(
+      span,
+      format!(
+        "the record name has a length of {}, which is larger than the {} Unicode code points \
+         allowed in an atom",
+        record_len, MAX_ATOM_LEN,
+      ),
+    ));
+  }
+  Ok(record)
+}
+
+fn add_bound(generics: &syn::Generics, bound: TokenStream) -> syn::Generics {
+  let mut generics = generics.clone();
+  for param in &mut generics.params {
+    if let syn::GenericParam::Type(param) = param {
+      param.bounds.push(syn::parse_quote!(#bound));
+    }
+  }
+  generics
+}
+
+/// Converts `CamelCase` to `snake_case`, keeping acronyms together: `HTTPRequest` becomes
+/// `http_request`.
+fn snake_case(name: &str) -> String {
+  let chars = name.chars().collect::<Vec<_>>();
+  let mut result = String::with_capacity(name.len() + 4);
+  for (index, &c) in chars.iter().enumerate() {
+    if c.is_uppercase() && index > 0 {
+      let previous = chars[index - 1];
+      let next_is_lowercase = chars.get(index + 1).map_or(false, |c| c.is_lowercase());
+      if previous.is_lowercase()
+        || previous.is_numeric()
+        || (previous.is_uppercase() && next_is_lowercase)
+      {
+        result.push('_');
+      }
+    }
+    result.extend(c.to_lowercase());
+  }
+  result
+}
@@ -0,0 +1,239 @@
+//! The `term!` macro, which builds a `Term` from Erlang literal syntax.
+//!
+//! Lowercase identifiers are atoms, identifiers starting with an uppercase letter are Rust
+//! variables and parenthesized expressions are Rust expressions. Both are converted with
+//! `IntoTerm`. Strings are charlists, like in Erlang, and `<<"...">>` builds a binary.
+
+use crate::MAX_ATOM_LEN;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+  braced, bracketed,
+  ext::IdentExt,
+  parenthesized,
+  parse::{Parse, ParseStream},
+  token, Token,
+};
+
+pub struct TermLiteral(pub TokenStream);
+
+impl Parse for TermLiteral {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let term = parse_term(input)?;
+    if !input.is_empty() {
+      return Err(input.error("expected the end of the term"));
+    }
+    Ok(TermLiteral(term))
+  }
+}
+
+fn parse_term(input: ParseStream) -> syn::Result<TokenStream> {
+  if input.peek(token::Brace) {
+    let content;
+    braced!(content in input);
+    let elements = parse_terms(&content)?;
+    Ok(quote! {
+      ::erlang_cnode::Term::Tuple(::erlang_cnode::Tuple(vec![#(#elements),*].into_boxed_slice()))
+    })
+  } else if input.peek(token::Bracket) {
+    let content;
+    bracketed!(content in input);
+    parse_list(&content)
+  } else if input.peek(Token![<<]) {
+    parse_binary(input)
+  } else if input.peek(Token![#]) {
+    input.parse::<Token![#]>()?;
+    let content;
+    braced!(content in input);
+    parse_map(&content)
+  } else if input.peek(token::Paren) {
+    let content;
+    parenthesized!(content in input);
+    let expression = content.parse::<syn::Expr>()?;
+    Ok(quote!(::erlang_cnode::IntoTerm::into_term(#expression)))
+  } else if input.peek(Token![-]) {
+    input.parse::<Token![-]>()?;
+    parse_number(input, true)
+  } else if input.peek(syn::LitInt) || input.peek(syn::LitFloat) {
+    parse_number(input, false)
+  } else if input.peek(syn::LitStr) {
+    let text = input.parse::<syn::LitStr>()?.value();
+    if text.is_empty() {
+      return Ok(quote!(::erlang_cnode::Term::Nil));
+    }
+    let characters = text.chars().map(|c| c as i32);
+    Ok(quote! {
+      ::erlang_cnode::Term::List(::erlang_cnode::List::new(vec![
+        #(::erlang_cnode::Term::Integer(#characters)),*
+      ]))
+    })
+  } else if input.peek(syn::LitBool) {
+    let value = input.parse::<syn::LitBool>()?;
+    atom(&value.value.to_string(), value.span)
+  } else if input.peek(syn::Ident::peek_any) {
+    let ident = input.call(syn::Ident::parse_any)?;
+    let name = ident.to_string();
+    if name.starts_with(char::is_uppercase) {
+      Ok(quote!(::erlang_cnode::IntoTerm::into_term(#ident)))
+    } else {
+      atom(&name, ident.span())
+    }
+  } else {
+    Err(input.error("expected a term"))
+  }
+}
+
+fn parse_terms(input: ParseStream) -> syn::Result<Vec<TokenStream>> {
+  let mut terms = Vec::new();
+  while !input.is_empty() {
+    terms.push(parse_term(input)?);
+    if input.is_empty() {
+      break;
+    }
+    input.parse::<Token![,]>()?;
+  }
+  Ok(terms)
+}
+
+fn parse_list(input: ParseStream) -> syn::Result<TokenStream> {
+  let mut elements = Vec::new();
+  let mut tail = quote!(::erlang_cnode::Term::Nil);
+  while !input.is_empty() {
+    elements.push(parse_term(input)?);
+    if input.peek(Token![|]) {
+      input.parse::<Token![|]>()?;
+      tail = parse_term(input)?;
+      if !input.is_empty() {
+        return Err(input.error("expected the end of the list after its tail"));
+      }
+      break;
+    }
+    if input.is_empty() {
+      break;
+    }
+    input.parse::<Token![,]>()?;
+  }
+
+  if elements.is_empty() {
+    Ok(quote!(::erlang_cnode::Term::Nil))
+  } else {
+    Ok(quote! {
+      ::erlang_cnode::Term::List(::erlang_cnode::List::improper(vec![#(#elements),*], #tail))
+    })
+  }
+}
+
+fn parse_binary(input: ParseStream) -> syn::Result<TokenStream> {
+  input.parse::<Token![<<]>()?;
+  let mut segments = Vec::new();
+  while !input.peek(Token![>>]) {
+    segments.push(parse_binary_segment(input)?);
+    if input.peek(Token![>>]) {
+      break;
+    }
+    input.parse::<Token![,]>()?;
+  }
+  input.parse::<Token![>>]>()?;
+  Ok(quote! {{
+    let mut bytes = ::std::vec::Vec::<u8>::new();
+    #(#segments)*
+    ::erlang_cnode::Term::Binary(::erlang_cnode::Binary(bytes.into_boxed_slice()))
+  }})
+}
+
+fn parse_binary_segment(input: ParseStream) -> syn::Result<TokenStream> {
+  if input.peek(syn::LitStr) {
+    let text = input.parse::<syn::LitStr>()?;
+    Ok(quote!(bytes.extend_from_slice(#text.as_bytes());))
+  } else if input.peek(syn::LitByteStr) {
+    let text = input.parse::<syn::LitByteStr>()?;
+    Ok(quote!(bytes.extend_from_slice(#text);))
+  } else if input.peek(syn::LitInt) {
+    let literal = input.parse::<syn::LitInt>()?;
+    let byte = literal.base10_parse::<u8>()?;
+    Ok(quote!(bytes.push(#byte);))
+  } else if input.peek(token::Paren) {
+    let content;
+    parenthesized!(content in input);
+    let expression = content.parse::<syn::Expr>()?;
+    Ok(quote!(bytes.extend_from_slice(::std::convert::AsRef::<[u8]>::as_ref(&#expression));))
+  } else if input.peek(syn::Ident) {
+    let ident = input.parse::<syn::Ident>()?;
+    if !ident.to_string().starts_with(char::is_uppercase) {
+      return Err(syn::Error::new(
+        ident.span(),
+        "expected a variable, which starts with an uppercase letter",
+      ));
+    }
+    Ok(quote!(bytes.extend_from_slice(::std::convert::AsRef::<[u8]>::as_ref(&#ident));))
+  } else {
+    Err(input.error("expected a string, a byte or a variable"))
+  }
+}
+
+fn parse_map(input: ParseStream) -> syn::Result<TokenStream> {
+  let mut entries = Vec::new();
+  while !input.is_empty() {
+    let key = parse_term(input)?;
+    input.parse::<Token![=>]>()?;
+    let value = parse_term(input)?;
+    entries.push(quote!((#key, #value)));
+    if input.is_empty() {
+      break;
+    }
+    input.parse::<Token![,]>()?;
+  }
+  Ok(quote! {
+    ::erlang_cnode::Term::Map(::erlang_cnode::Map(vec![#(#entries),*].into_boxed_slice()))
+  })
+}
+
+fn parse_number(input: ParseStream, is_negative: bool) -> syn::Result<TokenStream> {
+  let sign = if is_negative { quote!(-) } else { quote!() };
+  if input.peek(syn::LitFloat) {
+    let literal = input.parse::<syn::LitFloat>()?;
+    let value = literal.base10_parse::<f64>()?;
+    return Ok(quote!(::erlang_cnode::Term::Float(#sign #value)));
+  }
+
+  let literal = input.parse::<syn::LitInt>()?;
+  if !literal.suffix().is_empty() {
+    return Ok(quote!(::erlang_cnode::IntoTerm::into_term(#sign #literal)));
+  }
+  let magnitude = literal.base10_parse::<u64>()?;
+  let value = if is_negative {
+    -i128::from(magnitude)
+  } else {
+    i128::from(magnitude)
+  };
+  if value >= i128::from(i32::min_value()) && value <= i128::from(i32::max_value()) {
+    let value = value as i32;
+    Ok(quote!(::erlang_cnode::Term::Integer(#value)))
+  } else if value >= i128::from(i64::min_value()) && value <= i128::from(i64::max_value()) {
+    let value = value as i64;
+    Ok(quote!(::erlang_cnode::Term::from(#value)))
+  } else if is_negative {
+    Err(syn::Error::new(
+      literal.span(),
+      "negative integers must fit in 64 bits",
+    ))
+  } else {
+    Ok(quote!(::erlang_cnode::Term::from(#magnitude)))
+  }
+}
+
+fn atom(name: &str, span: proc_macro2::Span) -> syn::Result<TokenStream> {
+  let name_len = name.chars().count();
+  if name_len > MAX_ATOM_LEN {
+    return Err(syn::Error::new(
+      span,
+      format!(
+        "the atom has a length of {}, which is larger than the {} Unicode code points allowed",
+        name_len, MAX_ATOM_LEN,
+      ),
+    ));
+  }
+  Ok(quote! {
+    ::erlang_cnode::Term::Atom(::erlang_cnode::Atom::new(#name).unwrap())
+  })
+}
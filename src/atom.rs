@@ -28,14 +28,80 @@ impl borrow::Borrow<str> for Atom {
   }
 }
 
+/// The words that must be quoted to be read back as atoms.
+pub(crate) const RESERVED_WORDS: &[&str] = &[
+  "after", "and", "andalso", "band", "begin", "bnot", "bor", "bsl", "bsr", "bxor", "case", "catch",
+  "cond", "div", "else", "end", "fun", "if", "let", "maybe", "not", "of", "or", "orelse",
+  "receive", "rem", "try", "when", "xor",
+];
+
+impl Atom {
+  /// Returns whether the atom must be quoted to be read back, as in `'Hello'` or `'end'`.
+  pub fn needs_quotes(&self) -> bool {
+    let mut chars = self.0.chars();
+    let starts_lowercase = match chars.next() {
+      Some(c) => is_lowercase_start(c),
+      None => false,
+    };
+    !starts_lowercase || !chars.all(is_name_char) || RESERVED_WORDS.contains(&&*self.0)
+  }
+}
+
+pub(crate) fn is_lowercase_start(c: char) -> bool {
+  match c {
+    'a'..='z' | 'ß'..='ÿ' => c != '÷',
+    _ => false,
+  }
+}
+
+pub(crate) fn is_name_char(c: char) -> bool {
+  match c {
+    'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '@' => true,
+    'À'..='ÿ' => c != '×' && c != '÷',
+    _ => false,
+  }
+}
+
+/// Formats the atom as Erlang would print it, quoting it only when needed.
 impl fmt::Display for Atom {
   fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    if !self.needs_quotes() {
+      return formatter.write_str(&self.0);
+    }
     formatter.write_str("'")?;
-    formatter.write_str(&*self.0)?;
+    for c in self.0.chars() {
+      match c {
+        '\'' => formatter.write_str("\\'")?,
+        _ => write_escaped_char(formatter, c)?,
+      }
+    }
     formatter.write_str("'")
   }
 }
 
+/// Writes a character of a quoted atom or string, escaping it like `io_lib` does when printing as
+/// Latin-1, as with `~p`: characters above U+00FF are written as in `\x{43F}`.
+pub(crate) fn write_escaped_char(
+  formatter: &mut fmt::Formatter,
+  c: char,
+) -> result::Result<(), fmt::Error> {
+  match c {
+    '\\' => formatter.write_str("\\\\"),
+    '\n' => formatter.write_str("\\n"),
+    '\r' => formatter.write_str("\\r"),
+    '\t' => formatter.write_str("\\t"),
+    '\u{b}' => formatter.write_str("\\v"),
+    '\u{8}' => formatter.write_str("\\b"),
+    '\u{c}' => formatter.write_str("\\f"),
+    '\u{1b}' => formatter.write_str("\\e"),
+    '\u{7f}' => formatter.write_str("\\d"),
+    '\u{0}'..='\u{1f}' => write!(formatter, "\\^{}", (c as u8 + b'@') as char),
+    '\u{80}'..='\u{9f}' => write!(formatter, "\\{:o}", c as u32),
+    '\u{100}'..='\u{10ffff}' => write!(formatter, "\\x{{{:X}}}", c as u32),
+    _ => write!(formatter, "{}", c),
+  }
+}
+
 impl AtomCache {
   pub fn new() -> Self {
     AtomCache {
@@ -46,6 +112,16 @@ impl AtomCache {
   pub fn insert(&mut self, key: AtomCacheKey, atom: Atom) -> Option<Atom> {
     self.entries.insert(key, atom)
   }
+
+  pub fn get(&self, key: &AtomCacheKey) -> Option<&Atom> {
+    self.entries.get(key)
+  }
+}
+
+impl AtomCacheReferences {
+  pub fn new() -> Self {
+    AtomCacheReferences(Vec::new())
+  }
 }
 
 impl ops::Index<&AtomCacheKey> for AtomCache {
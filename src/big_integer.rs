@@ -0,0 +1,204 @@
+use crate::ty::*;
+use std::{cmp, fmt, result};
+
+impl BigInteger {
+  /// Creates a new `BigInteger` from its sign and its magnitude, given in base 256 with the least
+  /// significant digit first, as in the external term format.
+  ///
+  /// Leading zeros in the magnitude are dropped and zero is never negative.
+  pub fn new<Digits>(is_negative: bool, digits: Digits) -> Self
+  where
+    Digits: Into<Vec<u8>>,
+  {
+    let mut digits = digits.into();
+    while digits.last() == Some(&0) {
+      digits.pop();
+    }
+    BigInteger {
+      is_negative: is_negative && !digits.is_empty(),
+      digits: digits.into_boxed_slice(),
+    }
+  }
+
+  pub fn is_negative(&self) -> bool {
+    self.is_negative
+  }
+
+  /// Returns the magnitude in base 256, least significant digit first.
+  pub fn digits(&self) -> &[u8] {
+    &self.digits
+  }
+
+  /// Creates a `BigInteger` from digits in `radix`, most significant digit first.
+  pub(crate) fn from_radix(is_negative: bool, digits: &[u8], radix: u8) -> Self {
+    let mut magnitude = Vec::<u8>::new();
+    for &digit in digits {
+      let mut carry = u32::from(digit);
+      for byte in &mut magnitude {
+        let value = u32::from(*byte) * u32::from(radix) + carry;
+        *byte = value as u8;
+        carry = value >> 8;
+      }
+      while carry > 0 {
+        magnitude.push(carry as u8);
+        carry >>= 8;
+      }
+    }
+    BigInteger::new(is_negative, magnitude)
+  }
+
+  /// Creates a `BigInteger` from a finite float without a fractional part.
+  pub(crate) fn from_integral_f64(value: f64) -> Self {
+    if value.abs() < 9_223_372_036_854_775_808.0 {
+      return BigInteger::from(value as i64);
+    }
+    // The float is at least 2^63, so its exponent is positive and it is its mantissa shifted left.
+    let bits = value.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as usize - 1075;
+    let mantissa = (bits & 0xf_ffff_ffff_ffff) | (1 << 52);
+    let mut digits = vec![0; exponent / 8];
+    digits.extend_from_slice(&(u128::from(mantissa) << (exponent % 8)).to_le_bytes());
+    BigInteger::new(value.is_sign_negative(), digits)
+  }
+
+  fn magnitude_u64(&self) -> Option<u64> {
+    if self.digits.len() > 8 {
+      return None;
+    }
+    Some(
+      self
+        .digits
+        .iter()
+        .rev()
+        .fold(0, |magnitude, &digit| (magnitude << 8) | u64::from(digit)),
+    )
+  }
+}
+
+/// Formats the integer in decimal.
+impl fmt::Display for BigInteger {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    // Repeatedly divides the magnitude by 10^9, most significant digit first, collecting the
+    // remainders as groups of nine decimal digits.
+    const GROUP: u64 = 1_000_000_000;
+    let mut magnitude = self.digits.iter().rev().cloned().collect::<Vec<_>>();
+    let mut groups = Vec::new();
+    while !magnitude.is_empty() {
+      let mut remainder = 0u64;
+      for digit in &mut magnitude {
+        let value = (remainder << 8) | u64::from(*digit);
+        *digit = (value / GROUP) as u8;
+        remainder = value % GROUP;
+      }
+      groups.push(remainder);
+      let leading_zeros = magnitude.iter().take_while(|&&digit| digit == 0).count();
+      magnitude.drain(..leading_zeros);
+    }
+
+    let mut text = String::new();
+    match groups.split_last() {
+      Some((first, rest)) => {
+        text.push_str(&first.to_string());
+        for group in rest.iter().rev() {
+          text.push_str(&format!("{:09}", group));
+        }
+      }
+      None => text.push('0'),
+    }
+    formatter.pad_integral(!self.is_negative, "", &text)
+  }
+}
+
+/// Orders integers by their value.
+impl Ord for BigInteger {
+  fn cmp(&self, other: &Self) -> cmp::Ordering {
+    let magnitude_ordering = self
+      .digits
+      .len()
+      .cmp(&other.digits.len())
+      .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()));
+    match (self.is_negative, other.is_negative) {
+      (false, false) => magnitude_ordering,
+      (true, true) => magnitude_ordering.reverse(),
+      (false, true) => cmp::Ordering::Greater,
+      (true, false) => cmp::Ordering::Less,
+    }
+  }
+}
+
+impl PartialOrd for BigInteger {
+  fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl num_traits::ToPrimitive for BigInteger {
+  fn to_i64(&self) -> Option<i64> {
+    let magnitude = self.magnitude_u64()?;
+    if self.is_negative {
+      if magnitude <= i64::max_value() as u64 + 1 {
+        Some((magnitude as i64).wrapping_neg())
+      } else {
+        None
+      }
+    } else {
+      num_traits::NumCast::from(magnitude)
+    }
+  }
+
+  fn to_u64(&self) -> Option<u64> {
+    if self.is_negative {
+      None
+    } else {
+      self.magnitude_u64()
+    }
+  }
+
+  fn to_f64(&self) -> Option<f64> {
+    let magnitude = self.digits.iter().rev().fold(0.0, |magnitude, &digit| {
+      magnitude * 256.0 + f64::from(digit)
+    });
+    Some(if self.is_negative {
+      -magnitude
+    } else {
+      magnitude
+    })
+  }
+}
+
+impl From<i64> for BigInteger {
+  fn from(value: i64) -> Self {
+    let magnitude = if value < 0 {
+      (value as u64).wrapping_neg()
+    } else {
+      value as u64
+    };
+    BigInteger::new(value < 0, magnitude.to_le_bytes().to_vec())
+  }
+}
+
+impl From<u64> for BigInteger {
+  fn from(value: u64) -> Self {
+    BigInteger::new(false, value.to_le_bytes().to_vec())
+  }
+}
+
+#[cfg(feature = "num-bigint")]
+impl From<BigInteger> for num_bigint::BigInt {
+  fn from(value: BigInteger) -> Self {
+    let sign = if value.is_negative {
+      num_bigint::Sign::Minus
+    } else {
+      num_bigint::Sign::Plus
+    };
+    num_bigint::BigInt::from_bytes_le(sign, &value.digits)
+  }
+}
+
+#[cfg(feature = "num-bigint")]
+impl From<num_bigint::BigInt> for BigInteger {
+  fn from(value: num_bigint::BigInt) -> Self {
+    let (sign, digits) = value.to_bytes_le();
+    BigInteger::new(sign == num_bigint::Sign::Minus, digits)
+  }
+}
@@ -0,0 +1,45 @@
+use crate::{err::*, ty::*};
+
+impl BitBinary {
+  /// Creates a bitstring from its bytes, the last of which only holds `trailing_bits` bits, in its
+  /// most significant bits.
+  ///
+  /// `trailing_bits` must be between 1 and 7, since bitstrings made of whole bytes are binaries.
+  /// The unused bits of the last byte are cleared.
+  pub fn new<Bytes>(bytes: Bytes, trailing_bits: u8) -> Result<Self>
+  where
+    Bytes: Into<Vec<u8>>,
+  {
+    let mut bytes = bytes.into();
+    match bytes.last_mut() {
+      Some(last) if 0 < trailing_bits && trailing_bits < 8 => {
+        *last &= !(0xff >> trailing_bits);
+      }
+      _ => return Err(ErrorKind::InvalidBitBinary(bytes.len(), trailing_bits).into()),
+    }
+    Ok(BitBinary {
+      bytes: bytes.into_boxed_slice(),
+      trailing_bits,
+    })
+  }
+
+  /// Returns the bytes, including the last partial one.
+  pub fn bytes(&self) -> &[u8] {
+    &self.bytes
+  }
+
+  /// Returns the number of bits used in the last byte, between 1 and 7.
+  pub fn trailing_bits(&self) -> u8 {
+    self.trailing_bits
+  }
+
+  /// Returns the size in bits, like `bit_size/1`.
+  pub fn bit_len(&self) -> usize {
+    (self.bytes.len() - 1) * 8 + usize::from(self.trailing_bits)
+  }
+
+  /// Returns the value of the bits in the last byte, as written in `<<1,2:3>>`.
+  pub(crate) fn trailing_value(&self) -> u8 {
+    self.bytes[self.bytes.len() - 1] >> (8 - self.trailing_bits)
+  }
+}
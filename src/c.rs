@@ -23,8 +23,8 @@ impl Atom {
 impl Pid {
   pub fn from_c(c_pid: &ei::erlang_pid) -> Result<Self> {
     let node_name = Atom::from_c(&c_pid.node)?;
-    let node = Node::new(node_name, c_pid.creation)?;
-    Pid::new(node, c_pid.num, c_pid.serial)
+    let node = Node::new(node_name, c_pid.creation);
+    Ok(Pid::new(node, c_pid.num, c_pid.serial))
   }
 }
 
@@ -41,8 +41,9 @@ impl ControlMessage {
 
     match message.msgtype {
       ERL_LINK => unimplemented!(),
+      // The `from` of `SEND` messages is left unset by ei, as they do not carry a sender.
       ERL_SEND => Ok(ControlMessage::Send {
-        from: Pid::from_c(&message.from)?,
+        from: None,
         to: Pid::from_c(&message.to)?,
         trace_token: None,
       }),
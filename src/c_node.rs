@@ -1,4 +1,4 @@
-use crate::{c, err::*, name::NodeName, protocol, ty::*, x};
+use crate::{c, err::*, ext, name::NodeName, protocol, ty::*, x};
 use ei_sys as ei;
 use in_addr;
 use std::{borrow, ffi, i32, mem, net, os};
@@ -115,9 +115,17 @@ impl Listener {
   }
 }
 
+/// The largest number of bytes that the messages being received in fragments take by default.
+const DEFAULT_MAX_FRAGMENTS_LEN: usize = 64 * 1024 * 1024;
+
 pub struct Connection {
   tcp_stream: net::TcpStream,
   atom_cache: AtomCache,
+  outgoing_atom_cache: Option<AtomCache>,
+  fragments: Fragments,
+  fragmenter: Option<Fragmenter>,
+  read_options: ext::ReadOptions<'static>,
+  write_options: ext::WriteOptions,
 }
 
 impl Connection {
@@ -125,6 +133,11 @@ impl Connection {
     Connection {
       tcp_stream,
       atom_cache: AtomCache::new(),
+      outgoing_atom_cache: None,
+      fragments: Fragments::new(DEFAULT_MAX_FRAGMENTS_LEN),
+      fragmenter: None,
+      read_options: Default::default(),
+      write_options: Default::default(),
     }
   }
 
@@ -144,16 +157,123 @@ impl Connection {
           ei_sys::ERL_MSG => {
             let input = buffer.as_slice();
             let (input, ()) = protocol::read_version_magic(input)?;
-            let (input, ()) = protocol::read_distribution_header(input, &mut self.atom_cache)?;
-            let control_message = ControlMessage::from_c(&c_message)?;
-            let (_, message) = control_message.read_message(input, &self.atom_cache)?;
-            return Ok(message);
+            match protocol::read_packet(
+              input,
+              &mut self.atom_cache,
+              &mut self.fragments,
+              self.read_options,
+            )? {
+              protocol::Packet::Whole(input, atom_references) => {
+                let control_message = ControlMessage::from_c(&c_message)?;
+                let (_, message) =
+                  control_message.read_message(input, &atom_references, self.read_options)?;
+                return Ok(message);
+              }
+              protocol::Packet::Reassembled(bytes, atom_references) => {
+                let (input, control_message) = ControlMessage::read(&bytes, &atom_references)?;
+                let (_, message) =
+                  control_message.read_message(input, &atom_references, self.read_options)?;
+                return Ok(message);
+              }
+              protocol::Packet::Fragment => (),
+            }
           }
           result => panic!("unknown result from ei_xreceive_msg: {}", result),
         }
       }
     }
   }
+
+  /// Sets the options to read the messages that the peer sends, such as the largest compressed
+  /// message to accept.
+  pub fn set_read_options(&mut self, options: ext::ReadOptions<'static>) {
+    self.read_options = options;
+  }
+
+  /// Sets the options to write the messages that are sent to the peer, such as whether to compress
+  /// them.
+  pub fn set_write_options(&mut self, options: ext::WriteOptions) {
+    self.write_options = options;
+  }
+
+  /// Makes the messages that are sent to the peer start with a distribution header, so that they
+  /// refer to the atoms that were already sent instead of spelling them out again.
+  ///
+  /// The peer must have agreed to `DFLAG_DIST_HDR_ATOM_CACHE` during the handshake. Messages with a
+  /// distribution header are never compressed.
+  pub fn enable_outgoing_atom_cache(&mut self) {
+    if self.outgoing_atom_cache.is_none() {
+      self.outgoing_atom_cache = Some(AtomCache::new());
+    }
+  }
+
+  /// Sets the largest number of bytes that the messages being received in fragments can take
+  /// together, 64 MiB by default. A fragment that goes over it fails to be received, and its
+  /// message is dropped.
+  pub fn set_max_fragments_len(&mut self, max_len: usize) {
+    self.fragments.set_max_len(max_len);
+  }
+
+  /// Splits the messages that are sent to the peer and that are larger than `max_fragment_size`
+  /// bytes into fragments.
+  ///
+  /// The peer must have agreed to `DFLAG_FRAGMENTS` during the handshake. Fragments start with a
+  /// distribution header, so this also enables the outgoing atom cache.
+  ///
+  /// A send only writes the first fragment of a large message. Every send then writes one of the
+  /// fragments that are left, taking them from each large message in turn, so that smaller
+  /// messages are not held behind large ones. Call `flush_fragments` to write the fragments that
+  /// are left.
+  pub fn enable_fragmentation(&mut self, max_fragment_size: usize) {
+    self.enable_outgoing_atom_cache();
+    // The fragmenter is kept, so that its pending fragments are still sent and the ids of the
+    // messages it fragments keep increasing.
+    match &mut self.fragmenter {
+      Some(fragmenter) => fragmenter.set_max_fragment_size(max_fragment_size),
+      None => self.fragmenter = Some(Fragmenter::new(max_fragment_size)),
+    }
+  }
+
+  /// Returns whether fragments of large messages are left to send. See `enable_fragmentation`.
+  pub fn has_pending_fragments(&self) -> bool {
+    self
+      .fragmenter
+      .as_ref()
+      .map_or(false, Fragmenter::has_pending_fragments)
+  }
+
+  /// Writes the fragments of large messages that are left to send. See `enable_fragmentation`.
+  pub fn flush_fragments(&mut self) -> Result<()> {
+    match &mut self.fragmenter {
+      Some(fragmenter) => protocol::write_pending_fragments(&mut self.tcp_stream, fragmenter),
+      None => Ok(()),
+    }
+  }
+
+  /// Sends `term` to the process identified by `to`.
+  pub fn send(&mut self, to: &Pid, term: &Term) -> Result<()> {
+    protocol::write_send(
+      &mut self.tcp_stream,
+      to,
+      term,
+      self.write_options,
+      self.outgoing_atom_cache.as_mut(),
+      self.fragmenter.as_mut(),
+    )
+  }
+
+  /// Sends `term` to the process registered as `to_name` on the peer node, on behalf of `from`.
+  pub fn reg_send(&mut self, from: &Pid, to_name: &Atom, term: &Term) -> Result<()> {
+    protocol::write_registered_send(
+      &mut self.tcp_stream,
+      from,
+      to_name,
+      term,
+      self.write_options,
+      self.outgoing_atom_cache.as_mut(),
+      self.fragmenter.as_mut(),
+    )
+  }
 }
 
 #[cfg(unix)]
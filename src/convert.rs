@@ -0,0 +1,527 @@
+//! Typed conversions between Rust values and terms.
+//!
+//! `FromTerm` checks the shape of a term and fails with a `ConversionFailed` error that records
+//! where in the term the mismatch is, for example "element 2 of tuple: expected atom, got integer".
+//! `IntoTerm` cannot fail. Strings become binaries, `Option` uses the atom `undefined` for `None`
+//! and structs deriving these traits become record-style tuples tagged with their name.
+
+use crate::{err::*, ty::*};
+use std::{collections, fmt, hash, result, str};
+
+const NONE_ATOM: &str = "undefined";
+
+pub trait FromTerm: Sized {
+  fn from_term(term: &Term) -> Result<Self>;
+}
+
+pub trait IntoTerm {
+  fn into_term(self) -> Term;
+}
+
+impl fmt::Display for ConversionPathSegment {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    match self {
+      ConversionPathSegment::TupleElement(position) => {
+        write!(formatter, "element {} of tuple", position)
+      }
+      ConversionPathSegment::ListElement(position) => {
+        write!(formatter, "element {} of list", position)
+      }
+      ConversionPathSegment::MapKey(index) => write!(formatter, "key of entry {} of map", index),
+      ConversionPathSegment::MapValue(index) => {
+        write!(formatter, "value of entry {} of map", index)
+      }
+      ConversionPathSegment::RecordField { record, field } => {
+        write!(formatter, "field {} of record {}", field, record)
+      }
+    }
+  }
+}
+
+/// Writes the segments separated by commas and followed by a colon, or nothing if the path is
+/// empty, so that it can prefix the rest of an error message.
+impl fmt::Display for ConversionPath {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    let ConversionPath(segments) = self;
+    for (index, segment) in segments.iter().enumerate() {
+      if index > 0 {
+        formatter.write_str(", ")?;
+      }
+      fmt::Display::fmt(segment, formatter)?;
+    }
+    if !segments.is_empty() {
+      formatter.write_str(": ")?;
+    }
+    Ok(())
+  }
+}
+
+fn mismatch<Expected>(expected: Expected, term: &Term) -> Error
+where
+  Expected: Into<String>,
+{
+  ErrorKind::ConversionFailed(Default::default(), expected.into(), term.kind()).into()
+}
+
+/// Prepends `segment` to the path of a conversion error, leaving other errors untouched.
+fn within(segment: ConversionPathSegment) -> impl FnOnce(Error) -> Error {
+  move |mut error| {
+    if let ErrorKind::ConversionFailed(ConversionPath(ref mut segments), _, _) = error.0 {
+      segments.insert(0, segment);
+    }
+    error
+  }
+}
+
+fn atom(name: &str) -> Term {
+  Term::Atom(Atom::new(name).expect("atom literal is too long"))
+}
+
+impl FromTerm for Term {
+  fn from_term(term: &Term) -> Result<Self> {
+    Ok(term.clone())
+  }
+}
+
+impl IntoTerm for Term {
+  fn into_term(self) -> Term {
+    self
+  }
+}
+
+macro_rules! impl_term_conversions {
+  ($($ty:ident => $variant:ident, $expected:expr;)*) => {
+    $(
+      impl FromTerm for $ty {
+        fn from_term(term: &Term) -> Result<Self> {
+          match term {
+            Term::$variant(value) => Ok(value.clone()),
+            _ => Err(mismatch($expected, term)),
+          }
+        }
+      }
+
+      impl IntoTerm for $ty {
+        fn into_term(self) -> Term {
+          self.into()
+        }
+      }
+    )*
+  };
+}
+
+impl_term_conversions! {
+  Atom => Atom, "atom";
+  Pid => Pid, "pid";
+  Port => Port, "port";
+  Reference => Reference, "reference";
+  Tuple => Tuple, "tuple";
+  Binary => Binary, "binary";
+  BitBinary => BitBinary, "bitstring";
+  Map => Map, "map";
+  Export => Export, "external fun";
+  Fun => Fun, "fun";
+}
+
+macro_rules! impl_integer_conversions {
+  ($($ty:ident => $into:ident, $to_primitive:ident;)*) => {
+    $(
+      impl FromTerm for $ty {
+        fn from_term(term: &Term) -> Result<Self> {
+          let value = match term {
+            Term::Integer(value) => num_traits::ToPrimitive::$to_primitive(value),
+            Term::BigInteger(value) => num_traits::ToPrimitive::$to_primitive(value),
+            _ => None,
+          };
+          value.ok_or_else(|| mismatch(stringify!($ty), term))
+        }
+      }
+
+      impl IntoTerm for $ty {
+        fn into_term(self) -> Term {
+          Term::from(self as $into)
+        }
+      }
+    )*
+  };
+}
+
+impl_integer_conversions! {
+  i8 => i64, to_i8;
+  i16 => i64, to_i16;
+  i32 => i64, to_i32;
+  i64 => i64, to_i64;
+  isize => i64, to_isize;
+  u8 => u64, to_u8;
+  u16 => u64, to_u16;
+  u32 => u64, to_u32;
+  u64 => u64, to_u64;
+  usize => u64, to_usize;
+}
+
+impl FromTerm for BigInteger {
+  fn from_term(term: &Term) -> Result<Self> {
+    match term {
+      Term::Integer(value) => Ok(i64::from(*value).into()),
+      Term::BigInteger(value) => Ok(value.clone()),
+      _ => Err(mismatch("integer", term)),
+    }
+  }
+}
+
+impl IntoTerm for BigInteger {
+  fn into_term(self) -> Term {
+    self.into()
+  }
+}
+
+impl FromTerm for f64 {
+  fn from_term(term: &Term) -> Result<Self> {
+    match term {
+      Term::Float(value) => Ok(*value),
+      _ => Err(mismatch("float", term)),
+    }
+  }
+}
+
+impl IntoTerm for f64 {
+  fn into_term(self) -> Term {
+    Term::Float(self)
+  }
+}
+
+/// Rounds the float to the nearest `f32`, and fails if it is too large for one rather than reading
+/// it as an infinity, which Erlang floats cannot be.
+impl FromTerm for f32 {
+  fn from_term(term: &Term) -> Result<Self> {
+    let value = f64::from_term(term)? as f32;
+    if value.is_infinite() {
+      return Err(mismatch("float in the range of f32", term));
+    }
+    Ok(value)
+  }
+}
+
+impl IntoTerm for f32 {
+  fn into_term(self) -> Term {
+    Term::Float(self.into())
+  }
+}
+
+impl FromTerm for bool {
+  fn from_term(term: &Term) -> Result<Self> {
+    match term {
+      Term::Atom(atom) if atom.name() == "true" => Ok(true),
+      Term::Atom(atom) if atom.name() == "false" => Ok(false),
+      _ => Err(mismatch("boolean", term)),
+    }
+  }
+}
+
+impl IntoTerm for bool {
+  fn into_term(self) -> Term {
+    atom(if self { "true" } else { "false" })
+  }
+}
+
+/// Converts a UTF-8 binary or a charlist.
+impl FromTerm for String {
+  fn from_term(term: &Term) -> Result<Self> {
+    match term {
+      Term::Binary(Binary(bytes)) => match str::from_utf8(bytes) {
+        Ok(text) => Ok(text.to_owned()),
+        Err(_) => Err(mismatch("UTF-8 binary", term)),
+      },
+      Term::Nil => Ok(String::new()),
+      Term::List(list) if list.is_proper() => list
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+          let character = match element {
+            Term::Integer(value) if *value >= 0 => std::char::from_u32(*value as u32),
+            _ => None,
+          };
+          character
+            .ok_or_else(|| mismatch("character", element))
+            .map_err(within(ConversionPathSegment::ListElement(index + 1)))
+        })
+        .collect(),
+      _ => Err(mismatch("string", term)),
+    }
+  }
+}
+
+impl IntoTerm for String {
+  fn into_term(self) -> Term {
+    self.as_str().into_term()
+  }
+}
+
+impl IntoTerm for &str {
+  fn into_term(self) -> Term {
+    Binary(self.as_bytes().to_vec().into_boxed_slice()).into()
+  }
+}
+
+impl<T> FromTerm for Option<T>
+where
+  T: FromTerm,
+{
+  fn from_term(term: &Term) -> Result<Self> {
+    match term {
+      Term::Atom(atom) if atom.name() == NONE_ATOM => Ok(None),
+      _ => T::from_term(term).map(Some),
+    }
+  }
+}
+
+impl<T> IntoTerm for Option<T>
+where
+  T: IntoTerm,
+{
+  fn into_term(self) -> Term {
+    match self {
+      Some(value) => value.into_term(),
+      None => atom(NONE_ATOM),
+    }
+  }
+}
+
+impl<T> FromTerm for Vec<T>
+where
+  T: FromTerm,
+{
+  fn from_term(term: &Term) -> Result<Self> {
+    match term {
+      Term::Nil => Ok(Vec::new()),
+      Term::List(list) if list.is_proper() => list
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+          T::from_term(element).map_err(within(ConversionPathSegment::ListElement(index + 1)))
+        })
+        .collect(),
+      _ => Err(mismatch("proper list", term)),
+    }
+  }
+}
+
+impl<T> IntoTerm for Vec<T>
+where
+  T: IntoTerm,
+{
+  fn into_term(self) -> Term {
+    if self.is_empty() {
+      return Term::Nil;
+    }
+    List::new(
+      self
+        .into_iter()
+        .map(IntoTerm::into_term)
+        .collect::<Vec<_>>(),
+    )
+    .into()
+  }
+}
+
+impl<K, V, S> FromTerm for collections::HashMap<K, V, S>
+where
+  K: FromTerm + Eq + hash::Hash,
+  V: FromTerm,
+  S: hash::BuildHasher + Default,
+{
+  fn from_term(term: &Term) -> Result<Self> {
+    match term {
+      Term::Map(map) => map
+        .iter()
+        .enumerate()
+        .map(|(index, (key, value))| {
+          let key = K::from_term(key).map_err(within(ConversionPathSegment::MapKey(index)))?;
+          let value =
+            V::from_term(value).map_err(within(ConversionPathSegment::MapValue(index)))?;
+          Ok((key, value))
+        })
+        .collect(),
+      _ => Err(mismatch("map", term)),
+    }
+  }
+}
+
+impl<K, V, S> IntoTerm for collections::HashMap<K, V, S>
+where
+  K: IntoTerm,
+  V: IntoTerm,
+{
+  fn into_term(self) -> Term {
+    let entries = self
+      .into_iter()
+      .map(|(key, value)| (key.into_term(), value.into_term()))
+      .collect::<Vec<_>>();
+    Map(entries.into_boxed_slice()).into()
+  }
+}
+
+macro_rules! impl_tuple_conversions {
+  ($($len:expr => ($($position:tt $ty:ident),*);)*) => {
+    $(
+      impl<$($ty),*> FromTerm for ($($ty,)*)
+      where
+        $($ty: FromTerm,)*
+      {
+        fn from_term(term: &Term) -> Result<Self> {
+          match term {
+            Term::Tuple(Tuple(elements)) if elements.len() == $len => Ok(($(
+              $ty::from_term(&elements[$position])
+                .map_err(within(ConversionPathSegment::TupleElement($position + 1)))?,
+            )*)),
+            _ => Err(mismatch(format!("tuple of size {}", $len), term)),
+          }
+        }
+      }
+
+      impl<$($ty),*> IntoTerm for ($($ty,)*)
+      where
+        $($ty: IntoTerm,)*
+      {
+        fn into_term(self) -> Term {
+          Tuple(vec![$(self.$position.into_term()),*].into_boxed_slice()).into()
+        }
+      }
+    )*
+  };
+}
+
+impl_tuple_conversions! {
+  1 => (0 T0);
+  2 => (0 T0, 1 T1);
+  3 => (0 T0, 1 T1, 2 T2);
+  4 => (0 T0, 1 T1, 2 T2, 3 T3);
+  5 => (0 T0, 1 T1, 2 T2, 3 T3, 4 T4);
+  6 => (0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5);
+  7 => (0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6);
+  8 => (0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7);
+  9 => (0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7, 8 T8);
+  10 => (0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7, 8 T8, 9 T9);
+  11 => (0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7, 8 T8, 9 T9, 10 T10);
+  12 => (0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7, 8 T8, 9 T9, 10 T10, 11 T11);
+}
+
+/// Returns the fields of a record-style tuple after checking its tag and size. Used by the
+/// `FromTerm` derive.
+#[doc(hidden)]
+pub fn read_record<'term>(
+  term: &'term Term,
+  record: &'static str,
+  field_count: usize,
+) -> Result<&'term [Term]> {
+  match term {
+    Term::Tuple(Tuple(elements)) if elements.len() == field_count + 1 => match &elements[0] {
+      Term::Atom(tag) if tag.name() == record => Ok(&elements[1..]),
+      tag => Err(mismatch(format!("atom {}", record), tag))
+        .map_err(within(ConversionPathSegment::TupleElement(1))),
+    },
+    _ => Err(mismatch(
+      format!("record {} with {} fields", record, field_count),
+      term,
+    )),
+  }
+}
+
+/// Converts a field of a record read by `read_record`. Used by the `FromTerm` derive.
+#[doc(hidden)]
+pub fn read_record_field<T>(term: &Term, record: &'static str, field: &'static str) -> Result<T>
+where
+  T: FromTerm,
+{
+  T::from_term(term).map_err(within(ConversionPathSegment::RecordField { record, field }))
+}
+
+/// Builds a record-style tuple. Used by the `IntoTerm` derive, which checks that the record name
+/// is a valid atom.
+#[doc(hidden)]
+pub fn write_record(record: &'static str, fields: Vec<Term>) -> Term {
+  let mut elements = Vec::with_capacity(fields.len() + 1);
+  elements.push(atom(record));
+  elements.extend(fields);
+  Tuple(elements.into_boxed_slice()).into()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn path(error: Error) -> Vec<ConversionPathSegment> {
+    match error.0 {
+      ErrorKind::ConversionFailed(ConversionPath(segments), _, _) => segments,
+      kind => panic!("{:?}", kind),
+    }
+  }
+
+  #[test]
+  fn values_round_trip() {
+    fn assert_round_trip<T: FromTerm + IntoTerm + Clone + fmt::Debug + PartialEq>(value: T) {
+      assert_eq!(T::from_term(&value.clone().into_term()).unwrap(), value);
+    }
+
+    assert_round_trip(-7i8);
+    assert_round_trip(u64::max_value());
+    assert_round_trip(i64::min_value());
+    assert_round_trip(1.5f64);
+    assert_round_trip(true);
+    assert_round_trip("héllo".to_owned());
+    assert_round_trip(Some(3u16));
+    assert_round_trip(None::<u16>);
+    assert_round_trip(Vec::<i32>::new());
+    assert_round_trip(vec![(1u8, "a".to_owned()), (2, "b".to_owned())]);
+    let mut map = collections::HashMap::new();
+    map.insert("one".to_owned(), 1);
+    assert_round_trip(map);
+
+    assert_eq!(true.into_term(), atom("true"));
+    assert_eq!(None::<i32>.into_term(), atom(NONE_ATOM));
+    assert_eq!("ab".into_term(), Binary(Box::new(*b"ab")).into());
+    assert_eq!(f32::from_term(&Term::Float(0.1)).unwrap(), 0.1f32);
+    assert_eq!(
+      f32::from_term(&Term::Float(-f64::from(f32::MAX))).unwrap(),
+      f32::MIN
+    );
+    for &value in &[1e39, -1e39, f64::MAX] {
+      match f32::from_term(&Term::Float(value)) {
+        Err(Error(ErrorKind::ConversionFailed(_, expected, TermKind::Float), _)) => {
+          assert_eq!(expected, "float in the range of f32")
+        }
+        result => panic!("{}: {:?}", value, result),
+      }
+    }
+    // Strings can also be read from charlists.
+    let charlist = List::new(vec![Term::Integer(104), Term::Integer(105)]).into();
+    assert_eq!(String::from_term(&charlist).unwrap(), "hi");
+  }
+
+  #[test]
+  fn errors_locate_the_mismatch() {
+    let term = Tuple(
+      vec![
+        atom("ok"),
+        List::new(vec![Term::Integer(1), atom("foo")]).into(),
+      ]
+      .into_boxed_slice(),
+    )
+    .into();
+    let error = <(Atom, Vec<i32>)>::from_term(&term).unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "element 2 of tuple, element 2 of list: expected i32, got atom"
+    );
+    assert_eq!(
+      path(error),
+      [
+        ConversionPathSegment::TupleElement(2),
+        ConversionPathSegment::ListElement(2),
+      ]
+    );
+
+    assert!(path(u8::from_term(&Term::Integer(256)).unwrap_err()).is_empty());
+    assert!(path(i32::from_term(&atom("foo")).unwrap_err()).is_empty());
+  }
+}
@@ -0,0 +1,790 @@
+//! Serde data format for the external term format, on the deserialization side.
+//!
+//! This is the inverse of the mapping described in `ser`. Deserialization is lenient where it can
+//! be without ambiguity: strings can also be read from charlists and atoms, sequences from tuples
+//! and binaries, and `None` from the Elixir `nil` as well as `undefined`.
+
+use crate::{
+  err::*,
+  ext, protocol,
+  ser::{EnumFormat, SerdeOptions, StructFormat, NONE_ATOM},
+  ty::*,
+};
+use serde::de::{self, IntoDeserializer};
+use std::{fmt, slice, str};
+
+const ELIXIR_NONE_ATOM: &str = "nil";
+
+impl de::Error for Error {
+  fn custom<T: fmt::Display>(message: T) -> Self {
+    ErrorKind::Serde(message.to_string()).into()
+  }
+}
+
+pub fn from_term<'de, T>(term: &'de Term) -> Result<T>
+where
+  T: de::Deserialize<'de>,
+{
+  from_term_with_options(term, Default::default())
+}
+
+pub fn from_term_with_options<'de, T>(term: &'de Term, options: SerdeOptions) -> Result<T>
+where
+  T: de::Deserialize<'de>,
+{
+  T::deserialize(Deserializer::new(term, options))
+}
+
+/// Deserializes a value from the external term format, starting with the version byte.
+pub fn from_bytes<T>(input: &[u8]) -> Result<T>
+where
+  T: de::DeserializeOwned,
+{
+  from_bytes_with_options(input, Default::default())
+}
+
+pub fn from_bytes_with_options<T>(input: &[u8], options: SerdeOptions) -> Result<T>
+where
+  T: de::DeserializeOwned,
+{
+  let (input, ()) = protocol::read_version_magic(input)?;
+  let (_, term) = ext::read_outer_term(input, &AtomCacheReferences::new(), Default::default())?;
+  from_term_with_options(&term, options)
+}
+
+#[derive(Copy, Clone)]
+pub struct Deserializer<'de> {
+  term: &'de Term,
+  options: SerdeOptions,
+}
+
+impl<'de> Deserializer<'de> {
+  pub fn new(term: &'de Term, options: SerdeOptions) -> Self {
+    Deserializer { term, options }
+  }
+
+  fn with_term(self, term: &'de Term) -> Self {
+    Deserializer::new(term, self.options)
+  }
+
+  fn invalid_type<V>(self, visitor: &V) -> Error
+  where
+    V: de::Visitor<'de>,
+  {
+    de::Error::invalid_type(unexpected(self.term), visitor)
+  }
+
+  fn proper_elements(self, list: &'de List) -> Result<ListIter<'de>> {
+    if list.is_proper() {
+      Ok(list.iter())
+    } else {
+      Err(de::Error::custom(
+        "expected a proper list, got an improper list",
+      ))
+    }
+  }
+
+  fn visit_elements<I, V>(self, elements: I, visitor: V) -> Result<V::Value>
+  where
+    I: ExactSizeIterator<Item = &'de Term>,
+    V: de::Visitor<'de>,
+  {
+    let len = elements.len();
+    let mut seq = SeqDeserializer {
+      iter: elements,
+      options: self.options,
+    };
+    let value = visitor.visit_seq(&mut seq)?;
+    match seq.iter.len() {
+      0 => Ok(value),
+      remaining => Err(de::Error::invalid_length(
+        len - remaining,
+        &"fewer elements",
+      )),
+    }
+  }
+}
+
+fn unexpected(term: &Term) -> de::Unexpected {
+  match term {
+    Term::Nil => de::Unexpected::Other("empty list"),
+    Term::Integer(value) => de::Unexpected::Signed((*value).into()),
+    Term::BigInteger(_) => de::Unexpected::Other("big integer"),
+    Term::Float(value) => de::Unexpected::Float(*value),
+    Term::Atom(_) => de::Unexpected::Other("atom"),
+    Term::Pid(_) => de::Unexpected::Other("pid"),
+    Term::Port(_) => de::Unexpected::Other("port"),
+    Term::Reference(_) => de::Unexpected::Other("reference"),
+    Term::Tuple(_) => de::Unexpected::Other("tuple"),
+    Term::List(_) => de::Unexpected::Seq,
+    Term::Binary(Binary(bytes)) => de::Unexpected::Bytes(bytes),
+    Term::BitBinary(_) => de::Unexpected::Other("bitstring"),
+    Term::Map(_) => de::Unexpected::Map,
+    Term::Export(_) | Term::Fun(_) => de::Unexpected::Other("fun"),
+  }
+}
+
+fn is_none_atom(atom: &Atom) -> bool {
+  let name = atom.name();
+  name == NONE_ATOM || name == ELIXIR_NONE_ATOM
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+  type Error = Error;
+
+  fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Nil => visitor.visit_seq(SeqDeserializer {
+        iter: [].iter(),
+        options: self.options,
+      }),
+      Term::Integer(value) => visitor.visit_i64((*value).into()),
+      Term::BigInteger(value) => {
+        if let Some(value) = num_traits::ToPrimitive::to_i64(value) {
+          visitor.visit_i64(value)
+        } else if let Some(value) = num_traits::ToPrimitive::to_u64(value) {
+          visitor.visit_u64(value)
+        } else {
+          Err(self.invalid_type(&visitor))
+        }
+      }
+      Term::Float(value) => visitor.visit_f64(*value),
+      Term::Atom(atom) => match atom.name() {
+        "true" => visitor.visit_bool(true),
+        "false" => visitor.visit_bool(false),
+        _ if is_none_atom(atom) => visitor.visit_none(),
+        name => visitor.visit_borrowed_str(name),
+      },
+      Term::Tuple(Tuple(elements)) => self.visit_elements(elements.iter(), visitor),
+      Term::List(list) => self.visit_elements(self.proper_elements(list)?, visitor),
+      Term::Binary(Binary(bytes)) => match str::from_utf8(bytes) {
+        Ok(text) => visitor.visit_borrowed_str(text),
+        Err(_) => visitor.visit_borrowed_bytes(bytes),
+      },
+      Term::Map(_) => self.deserialize_map(visitor),
+      Term::Pid(_)
+      | Term::Port(_)
+      | Term::Reference(_)
+      | Term::BitBinary(_)
+      | Term::Export(_)
+      | Term::Fun(_) => Err(self.invalid_type(&visitor)),
+    }
+  }
+
+  fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Atom(atom) if atom.name() == "true" => visitor.visit_bool(true),
+      Term::Atom(atom) if atom.name() == "false" => visitor.visit_bool(false),
+      _ => Err(self.invalid_type(&visitor)),
+    }
+  }
+
+  fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Integer(value) if *value >= 0 => match std::char::from_u32(*value as u32) {
+        Some(value) => visitor.visit_char(value),
+        None => Err(self.invalid_type(&visitor)),
+      },
+      _ => self.deserialize_str(visitor),
+    }
+  }
+
+  fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Binary(Binary(bytes)) => match str::from_utf8(bytes) {
+        Ok(text) => visitor.visit_borrowed_str(text),
+        Err(_) => Err(de::Error::invalid_value(unexpected(self.term), &visitor)),
+      },
+      Term::Atom(atom) => visitor.visit_borrowed_str(atom.name()),
+      Term::Nil => visitor.visit_borrowed_str(""),
+      Term::List(list) => {
+        let text = self
+          .proper_elements(list)?
+          .map(|element| match element {
+            Term::Integer(value) => std::char::from_u32(*value as u32).filter(|_| *value >= 0),
+            _ => None,
+          })
+          .collect::<Option<String>>()
+          .ok_or_else(|| self.invalid_type(&visitor))?;
+        visitor.visit_string(text)
+      }
+      _ => Err(self.invalid_type(&visitor)),
+    }
+  }
+
+  fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    self.deserialize_str(visitor)
+  }
+
+  fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Binary(Binary(bytes)) => visitor.visit_borrowed_bytes(bytes),
+      Term::Nil => visitor.visit_borrowed_bytes(&[]),
+      Term::List(list) => {
+        let bytes = self
+          .proper_elements(list)?
+          .map(|element| match element {
+            Term::Integer(value) => num_traits::NumCast::from(*value),
+            _ => None,
+          })
+          .collect::<Option<Vec<u8>>>()
+          .ok_or_else(|| self.invalid_type(&visitor))?;
+        visitor.visit_byte_buf(bytes)
+      }
+      _ => Err(self.invalid_type(&visitor)),
+    }
+  }
+
+  fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    self.deserialize_bytes(visitor)
+  }
+
+  fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Atom(atom) if is_none_atom(atom) => visitor.visit_none(),
+      _ => visitor.visit_some(self),
+    }
+  }
+
+  fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Nil => visitor.visit_unit(),
+      Term::Tuple(Tuple(elements)) if elements.is_empty() => visitor.visit_unit(),
+      _ => Err(self.invalid_type(&visitor)),
+    }
+  }
+
+  fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Atom(atom) if atom.name() == name => visitor.visit_unit(),
+      _ => self.deserialize_unit(visitor),
+    }
+  }
+
+  fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    visitor.visit_newtype_struct(self)
+  }
+
+  fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Nil => self.visit_elements([].iter(), visitor),
+      Term::List(list) => self.visit_elements(self.proper_elements(list)?, visitor),
+      Term::Tuple(Tuple(elements)) => self.visit_elements(elements.iter(), visitor),
+      Term::Binary(Binary(bytes)) => visitor.visit_seq(ByteSeqDeserializer { iter: bytes.iter() }),
+      _ => Err(self.invalid_type(&visitor)),
+    }
+  }
+
+  fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    self.deserialize_seq(visitor)
+  }
+
+  fn deserialize_tuple_struct<V>(
+    self,
+    _name: &'static str,
+    _len: usize,
+    visitor: V,
+  ) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    self.deserialize_seq(visitor)
+  }
+
+  fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Map(map) => visitor.visit_map(MapDeserializer {
+        iter: map.iter(),
+        value: None,
+        options: self.options,
+      }),
+      _ => Err(self.invalid_type(&visitor)),
+    }
+  }
+
+  fn deserialize_struct<V>(
+    self,
+    name: &'static str,
+    fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.term {
+      Term::Tuple(Tuple(elements)) => match elements.split_first() {
+        Some((Term::Atom(tag), fields_elements))
+          if tag.name() == name && fields_elements.len() == fields.len() =>
+        {
+          self.visit_elements(fields_elements.iter(), visitor)
+        }
+        _ => {
+          let expected = format!("a record tagged '{}' with {} fields", name, fields.len());
+          Err(de::Error::invalid_value(
+            unexpected(self.term),
+            &expected.as_str(),
+          ))
+        }
+      },
+      Term::Map(_) => self.deserialize_map(visitor),
+      _ => Err(self.invalid_type(&visitor)),
+    }
+  }
+
+  fn deserialize_enum<V>(
+    self,
+    _name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    let (variant, data) = match self.term {
+      Term::Atom(_) => (self.term, VariantData::Unit),
+      Term::Tuple(Tuple(elements)) => match elements.split_first() {
+        Some((variant @ Term::Atom(_), data)) => (variant, VariantData::Elements(data)),
+        _ => return Err(self.invalid_type(&visitor)),
+      },
+      Term::Map(Map(entries)) => match &**entries {
+        [(variant @ Term::Atom(_), data)] => (variant, VariantData::Term(data)),
+        _ => return Err(self.invalid_type(&visitor)),
+      },
+      _ => return Err(self.invalid_type(&visitor)),
+    };
+
+    visitor.visit_enum(EnumDeserializer {
+      deserializer: self,
+      variant,
+      data,
+    })
+  }
+
+  fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    self.deserialize_str(visitor)
+  }
+
+  fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    visitor.visit_unit()
+  }
+
+  serde::forward_to_deserialize_any! {
+    i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64
+  }
+}
+
+struct SeqDeserializer<I> {
+  iter: I,
+  options: SerdeOptions,
+}
+
+impl<'de, I> de::SeqAccess<'de> for SeqDeserializer<I>
+where
+  I: ExactSizeIterator<Item = &'de Term>,
+{
+  type Error = Error;
+
+  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+  where
+    T: de::DeserializeSeed<'de>,
+  {
+    match self.iter.next() {
+      Some(term) => seed
+        .deserialize(Deserializer::new(term, self.options))
+        .map(Some),
+      None => Ok(None),
+    }
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.iter.len())
+  }
+}
+
+struct ByteSeqDeserializer<'de> {
+  iter: slice::Iter<'de, u8>,
+}
+
+impl<'de> de::SeqAccess<'de> for ByteSeqDeserializer<'de> {
+  type Error = Error;
+
+  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+  where
+    T: de::DeserializeSeed<'de>,
+  {
+    match self.iter.next() {
+      Some(&byte) => seed.deserialize(byte.into_deserializer()).map(Some),
+      None => Ok(None),
+    }
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.iter.len())
+  }
+}
+
+struct MapDeserializer<'de> {
+  iter: slice::Iter<'de, (Term, Term)>,
+  value: Option<&'de Term>,
+  options: SerdeOptions,
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer<'de> {
+  type Error = Error;
+
+  fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+  where
+    K: de::DeserializeSeed<'de>,
+  {
+    match self.iter.next() {
+      Some((key, value)) => {
+        self.value = Some(value);
+        seed
+          .deserialize(Deserializer::new(key, self.options))
+          .map(Some)
+      }
+      None => Ok(None),
+    }
+  }
+
+  fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+  where
+    V: de::DeserializeSeed<'de>,
+  {
+    let value = self
+      .value
+      .take()
+      .expect("next_value_seed called before next_key_seed");
+    seed.deserialize(Deserializer::new(value, self.options))
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.iter.len())
+  }
+}
+
+/// The data that follows the tag of an enum variant.
+enum VariantData<'de> {
+  /// A bare atom.
+  Unit,
+  /// The elements after the tag in a tagged tuple.
+  Elements(&'de [Term]),
+  /// The value of a single entry map.
+  Term(&'de Term),
+}
+
+struct EnumDeserializer<'de> {
+  deserializer: Deserializer<'de>,
+  variant: &'de Term,
+  data: VariantData<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer<'de> {
+  type Error = Error;
+  type Variant = Self;
+
+  fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+  where
+    V: de::DeserializeSeed<'de>,
+  {
+    let variant = seed.deserialize(self.deserializer.with_term(self.variant))?;
+    Ok((variant, self))
+  }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumDeserializer<'de> {
+  type Error = Error;
+
+  fn unit_variant(self) -> Result<()> {
+    match self.data {
+      VariantData::Unit | VariantData::Elements([]) => Ok(()),
+      VariantData::Elements(_) | VariantData::Term(_) => Err(de::Error::invalid_type(
+        de::Unexpected::NewtypeVariant,
+        &"unit variant",
+      )),
+    }
+  }
+
+  fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+  where
+    T: de::DeserializeSeed<'de>,
+  {
+    match self.data {
+      VariantData::Elements([term]) | VariantData::Term(term) => {
+        seed.deserialize(self.deserializer.with_term(term))
+      }
+      VariantData::Elements(elements) => Err(de::Error::invalid_length(
+        elements.len(),
+        &"newtype variant",
+      )),
+      VariantData::Unit => Err(de::Error::invalid_type(
+        de::Unexpected::UnitVariant,
+        &"newtype variant",
+      )),
+    }
+  }
+
+  fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    match self.data {
+      VariantData::Elements(elements) => self.deserializer.visit_elements(elements.iter(), visitor),
+      VariantData::Term(term) => {
+        de::Deserializer::deserialize_tuple(self.deserializer.with_term(term), len, visitor)
+      }
+      VariantData::Unit => Err(de::Error::invalid_type(
+        de::Unexpected::UnitVariant,
+        &"tuple variant",
+      )),
+    }
+  }
+
+  fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+  where
+    V: de::Visitor<'de>,
+  {
+    let options = self.deserializer.options;
+    match self.data {
+      VariantData::Elements([term])
+        if options.enum_format == EnumFormat::TaggedTuple
+          && options.struct_format == StructFormat::Map =>
+      {
+        de::Deserializer::deserialize_map(self.deserializer.with_term(term), visitor)
+      }
+      VariantData::Elements(elements) => self.deserializer.visit_elements(elements.iter(), visitor),
+      VariantData::Term(term) => {
+        de::Deserializer::deserialize_any(self.deserializer.with_term(term), visitor)
+      }
+      VariantData::Unit => Err(de::Error::invalid_type(
+        de::Unexpected::UnitVariant,
+        &"struct variant",
+      )),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ser::*;
+  use serde_derive::{Deserialize, Serialize};
+  use std::fmt::Debug;
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Person {
+    name: String,
+    age: u32,
+    nickname: Option<String>,
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  enum Shape {
+    Point,
+    Circle(f64),
+    Rectangle(f64, f64),
+    Polygon { sides: u8, length: f64 },
+  }
+
+  fn assert_round_trip<T>(value: &T, options: SerdeOptions) -> Term
+  where
+    T: Debug + PartialEq + serde::Serialize + serde::de::DeserializeOwned,
+  {
+    let term = to_term_with_options(value, options).unwrap();
+    assert_eq!(&from_term_with_options::<T>(&term, options).unwrap(), value);
+    let bytes = to_bytes_with_options(value, options).unwrap();
+    assert_eq!(
+      &from_bytes_with_options::<T>(&bytes, options).unwrap(),
+      value
+    );
+    term
+  }
+
+  fn options(struct_format: StructFormat, enum_format: EnumFormat) -> SerdeOptions {
+    SerdeOptions {
+      struct_format,
+      enum_format,
+    }
+  }
+
+  fn circle() -> Term {
+    Term::Atom(Atom::new("Circle").unwrap())
+  }
+
+  #[test]
+  fn structs_round_trip() {
+    let joe = Person {
+      name: "joe".to_owned(),
+      age: 42,
+      nickname: None,
+    };
+
+    let term = assert_round_trip(&joe, options(StructFormat::Map, EnumFormat::TaggedTuple));
+    match &term {
+      Term::Map(map) => {
+        let age = Term::Atom(Atom::new("age").unwrap());
+        assert_eq!(map.get(&age), Some(&Term::Integer(42)));
+      }
+      term => panic!("{:?}", term),
+    }
+
+    let term = assert_round_trip(&joe, options(StructFormat::Record, EnumFormat::TaggedTuple));
+    match &term {
+      Term::Tuple(Tuple(elements)) => {
+        assert_eq!(elements.len(), 4);
+        assert_eq!(elements[2], Term::Integer(42));
+        assert_eq!(elements[3], Term::Atom(Atom::new(NONE_ATOM).unwrap()));
+      }
+      term => panic!("{:?}", term),
+    }
+  }
+
+  #[test]
+  fn records_must_match_the_struct() {
+    let options = options(StructFormat::Record, EnumFormat::TaggedTuple);
+    let record = |tag: &str, fields: Vec<Term>| {
+      let mut elements = vec![Term::Atom(Atom::new(tag).unwrap())];
+      elements.extend(fields);
+      Term::from(Tuple(elements.into_boxed_slice()))
+    };
+    let name = || Term::from(Binary(b"joe".to_vec().into_boxed_slice()));
+    let undefined = || Term::Atom(Atom::new(NONE_ATOM).unwrap());
+
+    let joe = record("Person", vec![name(), Term::Integer(42), undefined()]);
+    assert!(from_term_with_options::<Person>(&joe, options).is_ok());
+    for term in &[
+      record("person", vec![name(), Term::Integer(42), undefined()]),
+      record("other", vec![name(), Term::Integer(42), undefined()]),
+      record("Person", vec![name(), Term::Integer(42)]),
+      record(
+        "Person",
+        vec![name(), Term::Integer(42), undefined(), undefined()],
+      ),
+      Tuple(vec![name(), Term::Integer(42), undefined()].into_boxed_slice()).into(),
+    ] {
+      match from_term_with_options::<Person>(term, options) {
+        Err(Error(ErrorKind::Serde(message), _)) => {
+          assert!(
+            message.contains("a record tagged 'Person' with 3 fields"),
+            "{}",
+            message
+          )
+        }
+        result => panic!("{}: {:?}", term, result),
+      }
+    }
+  }
+
+  #[test]
+  fn byte_vectors_are_lists() {
+    let bytes = vec![1u8, 2, 3];
+    let term = assert_round_trip(&bytes, Default::default());
+    assert_eq!(
+      term,
+      List::new(vec![Term::Integer(1), Term::Integer(2), Term::Integer(3)]).into()
+    );
+    let binary = Term::from(Binary(bytes.clone().into_boxed_slice()));
+    assert_eq!(from_term::<Vec<u8>>(&binary).unwrap(), bytes);
+  }
+
+  #[test]
+  fn enums_round_trip() {
+    let shapes = [
+      Shape::Point,
+      Shape::Circle(1.5),
+      Shape::Rectangle(2.0, 3.0),
+      Shape::Polygon {
+        sides: 6,
+        length: 1.0,
+      },
+    ];
+    for &enum_format in &[EnumFormat::TaggedTuple, EnumFormat::Map] {
+      for &struct_format in &[StructFormat::Map, StructFormat::Record] {
+        for shape in &shapes {
+          assert_round_trip(shape, options(struct_format, enum_format));
+        }
+      }
+    }
+
+    let term = assert_round_trip(&Shape::Circle(1.5), Default::default());
+    assert_eq!(
+      term,
+      Tuple(vec![circle(), Term::Float(1.5)].into_boxed_slice()).into()
+    );
+    let term = assert_round_trip(
+      &Shape::Circle(1.5),
+      options(StructFormat::Map, EnumFormat::Map),
+    );
+    assert_eq!(
+      term,
+      Map(vec![(circle(), Term::Float(1.5))].into_boxed_slice()).into()
+    );
+  }
+
+  #[test]
+  fn options_round_trip() {
+    let term = assert_round_trip(&None::<i32>, Default::default());
+    assert_eq!(term, Term::Atom(Atom::new(NONE_ATOM).unwrap()));
+    let term = assert_round_trip(&Some(7), Default::default());
+    assert_eq!(term, Term::Integer(7));
+    assert_round_trip(&vec![Some(1), None], Default::default());
+  }
+
+  #[test]
+  fn lists_in_the_tail_are_read_as_elements() {
+    let list = Term::List(List {
+      elements: vec![Term::Integer(1)].into_boxed_slice(),
+      tail: Box::new(List::new(vec![Term::Integer(2), Term::Integer(3)]).into()),
+    });
+    assert_eq!(from_term::<Vec<u8>>(&list).unwrap(), [1, 2, 3]);
+    assert_eq!(from_term::<(u8, u8, u8)>(&list).unwrap(), (1, 2, 3));
+  }
+}
@@ -53,6 +53,118 @@ error_chain! {
       ),
     }
 
+    EncodedLenOutOfRange(len: usize, max: u64) {
+      description("a term is too large to be encoded in the external term format"),
+      display(
+        "a term with a length of {} cannot be encoded because the largest length the external \
+         term format supports here is {}",
+        len,
+        max,
+      ),
+    }
+
+    UncompressedSizeOutOfRange(size: usize, max: usize) {
+      description("a compressed term is too large to be uncompressed"),
+      display(
+        "a compressed term has an uncompressed size of {}, which is larger than the maximum of {}",
+        size,
+        max,
+      ),
+    }
+
+    UncompressedSizeMismatch(expected: usize, actual: usize) {
+      description("a compressed term does not have the size it announces"),
+      display(
+        "a compressed term announces an uncompressed size of {}, but has a size of {}",
+        expected,
+        actual,
+      ),
+    }
+
+    InvalidFloat(text: String) {
+      description("a float in the old format is invalid"),
+      display("a float in the old format is invalid: {:?}", text),
+    }
+
+    AtomIsNotLatin1(atom: Atom) {
+      description("an atom cannot be encoded in Latin-1"),
+      display("the atom {} cannot be encoded in Latin-1", atom),
+    }
+
+    InvalidBitBinary(len: usize, trailing_bits: u8) {
+      description("a bitstring is invalid"),
+      display(
+        "a bitstring of {} bytes cannot have {} bits in its last byte, it must be non-empty and \
+         have between 1 and 7 bits in its last byte",
+        len,
+        trailing_bits,
+      ),
+    }
+
+    AtomCacheReferenceOutOfRange(index: u8, len: usize) {
+      description("an atom cache reference is out of range"),
+      display(
+        "an atom cache reference has index {}, but the distribution header only has {} references",
+        index,
+        len,
+      ),
+    }
+
+    MissingAtomCacheEntry(key: AtomCacheKey) {
+      description("a distribution header refers to an atom cache entry that does not exist"),
+      display(
+        "a distribution header refers to the atom cache entry {} of segment {:?}, which does not \
+         exist",
+        key.internal_index,
+        key.segment_index,
+      ),
+    }
+
+    TrailingBytes(len: usize) {
+      description("a binary has bytes after the term it encodes"),
+      display("a binary has {} bytes after the term it encodes", len),
+    }
+
+    UnknownAtom(atom: Atom) {
+      description("a term contains an atom that is not known"),
+      display("a term contains the atom {}, which is not known", atom),
+    }
+
+    CompressionLevelOutOfRange(level: u32) {
+      description("a compression level is out of range"),
+      display("the compression level must be between 0 and 9, but got {}", level),
+    }
+
+    FragmentsTooLarge(len: usize, max: usize) {
+      description("the fragments of messages take too much memory"),
+      display(
+        "the fragments of messages being received would take {} bytes, which is more than the \
+         maximum of {}",
+        len,
+        max,
+      ),
+    }
+
+    UnknownFragmentSequence(sequence_id: u64) {
+      description("a fragment continues a message that was not started"),
+      display("a fragment continues the message {}, which was not started", sequence_id),
+    }
+
+    DuplicateFragmentSequence(sequence_id: u64) {
+      description("a fragment starts a message that was already started"),
+      display("a fragment starts the message {}, which was already started", sequence_id),
+    }
+
+    UnexpectedFragment(sequence_id: u64, expected: u64, actual: u64) {
+      description("a fragment is out of order"),
+      display(
+        "expected fragment {} of the message {}, but got fragment {}",
+        expected,
+        sequence_id,
+        actual,
+      ),
+    }
+
     NameLengthOutOfRange(name: Box<[u8]>, name_kind: NameKind) {
     }
 
@@ -69,6 +181,26 @@ error_chain! {
       ),
     }
 
+    Serde(message: String) {
+      description("a value could not be serialized or deserialized"),
+      display("{}", message),
+    }
+
+    ConversionFailed(path: ConversionPath, expected: String, found: TermKind) {
+      description("a term could not be converted"),
+      display("{}expected {}, got {}", path, expected, found),
+    }
+
+    HashRangeOutOfRange(range: u64) {
+      description("a hash range is out of range"),
+      display("the range of a hash must be between 1 and 2^32, but got {}", range),
+    }
+
+    Syntax(line: usize, column: usize, message: String) {
+      description("a term could not be parsed"),
+      display("{}:{}: {}", line, column, message),
+    }
+
     RunawayAtom(atom: Box<[os::raw::c_char]>) {
       description("an atom is invalid because it is not terminated by a null byte"),
       display("an atom is invalid because it is not terminated by a null byte: {:?}", atom),
@@ -83,5 +215,10 @@ error_chain! {
       description("a PID is out of range"),
       display("a PID from node {} is out of range: {:x}+{:x}", node.name, id, serial),
     }
+
+    PortOutOfRange(node: Node, id: u64) {
+      description("a port is out of range"),
+      display("a port from node {} is out of range: {:x}", node.name, id),
+    }
   }
 }
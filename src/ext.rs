@@ -7,12 +7,103 @@
 //! of bound access rather than risking an out of bound access in case of an incorrect
 //! implementation.
 
-use crate::{err::*, read, ty::*};
+use crate::{convert::FromTerm, err::*, order, protocol, read, ty::*, write};
 use ei_sys as ei;
-use std::str;
+use flate2::{bufread::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+  collections::{HashMap, HashSet},
+  io::{self, Read},
+  str,
+};
+
+/// Tags that are too recent to be exported by `ei_sys`.
+const NEWER_REFERENCE_EXT: u8 = b'Z';
+const NEW_PORT_EXT: u8 = b'Y';
+const V4_PORT_EXT: u8 = b'x';
+
+/// The tag of a term compressed with zlib, which is only valid as the outermost term.
+const COMPRESSED: u8 = 80;
+
+/// Options for `binary_to_term_with_options` and for reading the messages of a connection.
+#[derive(Debug, Copy, Clone)]
+pub struct ReadOptions<'atoms> {
+  /// The largest size, in bytes, that a compressed term may have once uncompressed. Larger terms
+  /// are rejected before they are uncompressed, so that a small message cannot exhaust memory.
+  pub max_uncompressed_size: usize,
+  /// The atoms that terms may contain, or `None` to accept any atom. This is the equivalent of the
+  /// `safe` option of `binary_to_term/2`, for data that comes from an untrusted source and that
+  /// should only be matched against a known set of atoms.
+  pub known_atoms: Option<&'atoms HashSet<Atom>>,
+  /// The encoding of the atoms of distribution headers, which have no tag to tell it. Peers that do
+  /// not set the UTF-8 atoms distribution flag send them in Latin-1, like they need
+  /// `WriteOptions::atom_encoding` to be `AtomEncoding::Latin1`.
+  pub atom_encoding: AtomEncoding,
+}
+
+impl<'atoms> Default for ReadOptions<'atoms> {
+  fn default() -> Self {
+    ReadOptions {
+      max_uncompressed_size: 64 * 1024 * 1024,
+      known_atoms: None,
+      atom_encoding: AtomEncoding::Utf8,
+    }
+  }
+}
+
+/// Options for `write_term`, for the formats that depend on what the peer supports.
+#[derive(Debug, Copy, Clone)]
+pub struct WriteOptions {
+  /// The format of node creations in pids, ports and references. Peers that do not support 32-bit
+  /// creations need `CreationFormat::Old`, in which case the legacy limits on creations and pid ids
+  /// apply.
+  pub creation_format: CreationFormat,
+  /// The encoding of atoms. Peers that do not set the UTF-8 atoms distribution flag need
+  /// `AtomEncoding::Latin1`, in which case atoms outside of Latin-1 cannot be written.
+  pub atom_encoding: AtomEncoding,
+  /// The format of floats. Peers that do not set the new floats distribution flag need
+  /// `FloatFormat::Old`, like `term_to_binary/2` with `{minor_version, 0}`.
+  pub float_format: FloatFormat,
+  /// The zlib level, from 0 to 9, to compress the outermost term at, or `None` to never compress.
+  /// Like with `term_to_binary/2`, terms that compression does not make smaller are written
+  /// uncompressed.
+  pub compression_level: Option<u32>,
+}
+
+impl Default for WriteOptions {
+  fn default() -> Self {
+    WriteOptions {
+      creation_format: CreationFormat::New,
+      atom_encoding: AtomEncoding::Utf8,
+      float_format: FloatFormat::New,
+      compression_level: None,
+    }
+  }
+}
+
+/// The state of the writing of a term.
+#[derive(Copy, Clone)]
+struct Encoder<'atoms> {
+  options: WriteOptions,
+  /// The atoms that the distribution header of the message refers to, with their index.
+  atom_indices: Option<&'atoms HashMap<Atom, u8>>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum AtomEncoding {
+  Latin1,
+  Utf8,
+}
 
 #[derive(Debug, Copy, Clone)]
-enum CreationFormat {
+pub enum FloatFormat {
+  /// A 31-byte string, as printed by `printf("%.20e")` and padded with null bytes.
+  Old,
+  /// 8 bytes, in the IEEE 754 big endian format.
+  New,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum CreationFormat {
   Old,
   New,
 }
@@ -24,6 +115,33 @@ impl CreationFormat {
       CreationFormat::New => read::be_u32(input),
     }
   }
+
+  fn write<W: io::Write>(self, output: &mut W, node: &Node) -> Result<()> {
+    const OLD_SERIAL_NUMBER_MAX: u32 = (1 << 2) - 1;
+
+    match self {
+      CreationFormat::Old if node.serial_number > OLD_SERIAL_NUMBER_MAX => {
+        Err(ErrorKind::NodeSerialNumberOutOfRange(node.name.clone(), node.serial_number).into())
+      }
+      CreationFormat::Old => write::be_u8(output, node.serial_number as u8),
+      CreationFormat::New => write::be_u32(output, node.serial_number),
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum PortIdFormat {
+  Regular,
+  V4,
+}
+
+impl PortIdFormat {
+  fn read(self, input: &[u8]) -> read::IResult<u64> {
+    match self {
+      PortIdFormat::Regular => read::be_u32::<u64>(input),
+      PortIdFormat::V4 => read::be_u64(input),
+    }
+  }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -58,34 +176,202 @@ impl TupleSizeFormat {
   }
 }
 
+/// Decodes a term encoded like with `term_to_binary/1`, starting with the version byte and
+/// possibly compressed.
+pub fn binary_to_term(input: &[u8]) -> Result<Term> {
+  binary_to_term_with_options(input, Default::default())
+}
+
+/// Decodes a term encoded like with `term_to_binary/1`, like `binary_to_term/2` does.
+///
+/// Atom cache references are not allowed since there is no connection to share a cache with, and
+/// the whole input must be used by the term.
+pub fn binary_to_term_with_options(input: &[u8], options: ReadOptions) -> Result<Term> {
+  let (input, ()) = protocol::read_version_magic(input)?;
+  let (input, term) = read_outer_term(input, &AtomCacheReferences::new(), options)?;
+  if !input.is_empty() {
+    return Err(ErrorKind::TrailingBytes(input.len()).into());
+  }
+  Ok(term)
+}
+
+/// Encodes a term like `term_to_binary/2`, starting with the version byte and compressed if
+/// `options` asks for it.
+pub fn term_to_binary(term: &Term, options: WriteOptions) -> Result<Vec<u8>> {
+  let mut output = Vec::new();
+  protocol::write_version_magic(&mut output)?;
+  write_outer_term(&mut output, term, options)?;
+  Ok(output)
+}
+
+/// Reads the outermost term of a message or of a binary, which may be compressed, right after the
+/// version magic.
+pub fn read_outer_term<'input>(
+  input: &'input [u8],
+  atom_references: &AtomCacheReferences,
+  options: ReadOptions,
+) -> read::IResult<'input, Term> {
+  let (input, term) = match input.split_first() {
+    Some((&COMPRESSED, input)) => read_compressed(input, atom_references, options)?,
+    _ => read_term(input, atom_references)?,
+  };
+  if let Some(known_atoms) = options.known_atoms {
+    check_known_atoms(&term, known_atoms)?;
+  }
+  Ok((input, term))
+}
+
+/// Checks that every atom of the term is in `known_atoms`.
+fn check_known_atoms(term: &Term, known_atoms: &HashSet<Atom>) -> Result<()> {
+  for_each_atom(term, &mut |atom| {
+    if known_atoms.contains(atom) {
+      Ok(())
+    } else {
+      Err(ErrorKind::UnknownAtom(atom.clone()).into())
+    }
+  })
+}
+
+/// Calls `f` on every atom of the term in the order they are written, including the names of the
+/// nodes of pids, ports and references.
+pub(crate) fn for_each_atom<F>(term: &Term, f: &mut F) -> Result<()>
+where
+  F: FnMut(&Atom) -> Result<()>,
+{
+  match term {
+    Term::Nil
+    | Term::Integer(_)
+    | Term::BigInteger(_)
+    | Term::Float(_)
+    | Term::Binary(_)
+    | Term::BitBinary(_) => Ok(()),
+    Term::Atom(atom) => f(atom),
+    Term::Pid(pid) => f(&pid.node.name),
+    Term::Port(port) => f(&port.node.name),
+    Term::Reference(reference) => f(&reference.node.name),
+    Term::Tuple(Tuple(elements)) => elements
+      .iter()
+      .try_for_each(|element| for_each_atom(element, f)),
+    Term::List(list) => {
+      list
+        .iter()
+        .try_for_each(|element| for_each_atom(element, f))?;
+      for_each_atom(list.tail(), f)
+    }
+    Term::Map(Map(entries)) => entries.iter().try_for_each(|(key, value)| {
+      for_each_atom(key, f)?;
+      for_each_atom(value, f)
+    }),
+    Term::Export(export) => {
+      f(&export.module)?;
+      f(&export.function)
+    }
+    Term::Fun(fun) => {
+      f(&fun.module)?;
+      f(&fun.pid.node.name)?;
+      fun
+        .free_vars
+        .iter()
+        .try_for_each(|free_var| for_each_atom(free_var, f))
+    }
+  }
+}
+
+fn read_compressed<'input>(
+  input: &'input [u8],
+  atom_references: &AtomCacheReferences,
+  options: ReadOptions,
+) -> read::IResult<'input, Term> {
+  let (input, size) = read::be_u32::<u32>(input)?;
+  let size = cast_len(size)?;
+  if size > options.max_uncompressed_size {
+    return Err(ErrorKind::UncompressedSizeOutOfRange(size, options.max_uncompressed_size).into());
+  }
+
+  // Reads one byte past the announced size, so that a stream that is longer is caught without
+  // uncompressing all of it.
+  let mut decoder = ZlibDecoder::new(input);
+  let mut uncompressed = Vec::with_capacity(size);
+  (&mut decoder)
+    .take(size as u64 + 1)
+    .read_to_end(&mut uncompressed)?;
+  if uncompressed.len() != size {
+    return Err(ErrorKind::UncompressedSizeMismatch(size, uncompressed.len()).into());
+  }
+
+  let (rest, term) = read_term(&uncompressed, atom_references)?;
+  if !rest.is_empty() {
+    return Err(ErrorKind::UncompressedSizeMismatch(size, size - rest.len()).into());
+  }
+  Ok((decoder.into_inner(), term))
+}
+
 pub fn read_term<'input>(
   input: &'input [u8],
-  atom_cache: &AtomCache,
+  atom_references: &AtomCacheReferences,
 ) -> read::IResult<'input, Term> {
   let (input, tag) = read::be_u8(input)?;
   match tag {
-    ei::ATOM_CACHE_REF => unimplemented!("ATOM_CACHE_REF"),
+    ei::ATOM_CACHE_REF => read_atom_cache_ref(input, atom_references),
     ei::NIL_EXT => Ok((input, Term::Nil)),
     ei::SMALL_INTEGER_EXT => read_small_integer(input),
     ei::INTEGER_EXT => read_integer(input),
     ei::SMALL_BIG_EXT => read_small_big_integer(input),
     ei::LARGE_BIG_EXT => read_large_big_integer(input),
-    ei::REFERENCE_EXT => read_reference(input, atom_cache),
-    ei::FLOAT_EXT => unimplemented!("FLOAT_EXT"),
+    ei::REFERENCE_EXT => read_reference(input, atom_references),
+    ei::NEW_REFERENCE_EXT => read_new_reference(input, CreationFormat::Old, atom_references),
+    NEWER_REFERENCE_EXT => read_new_reference(input, CreationFormat::New, atom_references),
+    ei::FLOAT_EXT => read_float(input),
     ei::NEW_FLOAT_EXT => read_new_float(input),
     ei::ATOM_UTF8_EXT => read_atom_utf8(input, AtomSizeFormat::Regular),
     ei::SMALL_ATOM_UTF8_EXT => read_atom_utf8(input, AtomSizeFormat::Small),
-    ei::PID_EXT => read_pid(input, CreationFormat::Old, atom_cache),
-    ei::NEW_PID_EXT => read_pid(input, CreationFormat::New, atom_cache),
-    ei::SMALL_TUPLE_EXT => read_tuple(input, TupleSizeFormat::Small, atom_cache),
-    ei::LARGE_TUPLE_EXT => read_tuple(input, TupleSizeFormat::Large, atom_cache),
-    ei::LIST_EXT => read_list(input, atom_cache),
+    ei::ATOM_EXT => read_atom_latin1(input, AtomSizeFormat::Regular),
+    ei::SMALL_ATOM_EXT => read_atom_latin1(input, AtomSizeFormat::Small),
+    ei::PID_EXT => read_pid(input, CreationFormat::Old, atom_references),
+    ei::NEW_PID_EXT => read_pid(input, CreationFormat::New, atom_references),
+    ei::PORT_EXT => read_port(
+      input,
+      PortIdFormat::Regular,
+      CreationFormat::Old,
+      atom_references,
+    ),
+    NEW_PORT_EXT => read_port(
+      input,
+      PortIdFormat::Regular,
+      CreationFormat::New,
+      atom_references,
+    ),
+    V4_PORT_EXT => read_port(
+      input,
+      PortIdFormat::V4,
+      CreationFormat::New,
+      atom_references,
+    ),
+    ei::SMALL_TUPLE_EXT => read_tuple(input, TupleSizeFormat::Small, atom_references),
+    ei::LARGE_TUPLE_EXT => read_tuple(input, TupleSizeFormat::Large, atom_references),
+    ei::LIST_EXT => read_list(input, atom_references),
     ei::STRING_EXT => read_string(input),
     ei::BINARY_EXT => read_binary(input),
+    ei::BIT_BINARY_EXT => read_bit_binary(input),
+    ei::MAP_EXT => read_map(input, atom_references),
+    ei::EXPORT_EXT => read_export(input, atom_references),
+    ei::NEW_FUN_EXT => read_new_fun(input, atom_references),
     _ => Err(ErrorKind::UnknownTermTag(tag).into()),
   }
 }
 
+fn read_atom_cache_ref<'input>(
+  input: &'input [u8],
+  atom_references: &AtomCacheReferences,
+) -> read::IResult<'input, Term> {
+  let (input, index) = read::be_u8::<u8>(input)?;
+  let AtomCacheReferences(atoms) = atom_references;
+  match atoms.get(usize::from(index)) {
+    Some(atom) => Ok((input, atom.clone().into())),
+    None => Err(ErrorKind::AtomCacheReferenceOutOfRange(index, atoms.len()).into()),
+  }
+}
+
 fn read_small_integer(input: &[u8]) -> read::IResult<Term> {
   let (input, value) = read::be_u8::<i32>(input)?;
   Ok((input, Term::Integer(value)))
@@ -96,55 +382,137 @@ fn read_integer(input: &[u8]) -> read::IResult<Term> {
   Ok((input, Term::Integer(value)))
 }
 
-fn read_small_big_integer(_input: &[u8]) -> read::IResult<Term> {
-  unimplemented!("SMALL_BIG_EXT")
+fn read_small_big_integer(input: &[u8]) -> read::IResult<Term> {
+  let (input, len) = read::be_u8::<usize>(input)?;
+  read_big_integer_digits(input, len)
+}
+
+fn read_large_big_integer(input: &[u8]) -> read::IResult<Term> {
+  let (input, len) = read::be_u32::<u32>(input)?;
+  read_big_integer_digits(input, cast_len(len)?)
+}
+
+fn read_big_integer_digits(input: &[u8], len: usize) -> read::IResult<Term> {
+  let (input, sign) = read::be_u8::<u8>(input)?;
+  let (input, digits) = read::take(input, len)?;
+  Ok((input, BigInteger::new(sign != 0, digits).into()))
 }
 
-fn read_large_big_integer(_input: &[u8]) -> read::IResult<Term> {
-  unimplemented!("LARGE_BIG_EXT")
+/// The size of a float in the old format, including the padding.
+const OLD_FLOAT_LEN: usize = 31;
+
+fn read_float(input: &[u8]) -> read::IResult<Term> {
+  let (input, float_bytes) = read::take(input, OLD_FLOAT_LEN)?;
+  let text = str::from_utf8(float_bytes)?.trim_end_matches('\0').trim();
+  match text.parse::<f64>() {
+    Ok(value) if value.is_finite() => Ok((input, Term::Float(value))),
+    _ => Err(ErrorKind::InvalidFloat(text.into()).into()),
+  }
 }
 
 fn read_new_float(input: &[u8]) -> read::IResult<Term> {
   let (input, value) = read::be_f64(input)?;
-  Ok((&input[31..], Term::Float(value)))
+  Ok((input, Term::Float(value)))
 }
 
 fn read_atom_utf8(input: &[u8], size_format: AtomSizeFormat) -> read::IResult<Term> {
   let (input, size) = size_format.read(input)?;
   let (input, atom_bytes) = read::take(input, size)?;
-  Ok((input, Atom::new(str::from_utf8(atom_bytes)?)?.into()))
+  Ok((input, decode_atom(atom_bytes, AtomEncoding::Utf8)?.into()))
+}
+
+fn read_atom_latin1(input: &[u8], size_format: AtomSizeFormat) -> read::IResult<Term> {
+  let (input, size) = size_format.read(input)?;
+  let (input, atom_bytes) = read::take(input, size)?;
+  Ok((input, decode_atom(atom_bytes, AtomEncoding::Latin1)?.into()))
+}
+
+/// Decodes the text of an atom. In Latin-1, each byte is the code point of a character.
+pub(crate) fn decode_atom(bytes: &[u8], encoding: AtomEncoding) -> Result<Atom> {
+  match encoding {
+    AtomEncoding::Latin1 => Atom::new(
+      bytes
+        .iter()
+        .map(|&byte| char::from(byte))
+        .collect::<String>(),
+    ),
+    AtomEncoding::Utf8 => Atom::new(str::from_utf8(bytes)?),
+  }
 }
 
 fn read_pid<'input>(
   input: &'input [u8],
   creation_format: CreationFormat,
-  atom_cache: &AtomCache,
+  atom_references: &AtomCacheReferences,
 ) -> read::IResult<'input, Term> {
-  let (input, node_name) = read_node_name(input, atom_cache)?;
+  let (input, node_name) = read_node_name(input, atom_references)?;
   let (input, id) = read::be_u32(input)?;
   let (input, serial) = read::be_u32(input)?;
   let (input, node_serial_number) = creation_format.read(input)?;
-  let node = Node::new(node_name, node_serial_number)?;
-  let pid = Pid::new(node, id, serial)?;
-  Ok((input, pid.into()))
+  let node = Node::new(node_name, node_serial_number);
+  Ok((input, Pid::new(node, id, serial).into()))
+}
+
+fn read_port<'input>(
+  input: &'input [u8],
+  id_format: PortIdFormat,
+  creation_format: CreationFormat,
+  atom_references: &AtomCacheReferences,
+) -> read::IResult<'input, Term> {
+  let (input, node_name) = read_node_name(input, atom_references)?;
+  let (input, id) = id_format.read(input)?;
+  let (input, node_serial_number) = creation_format.read(input)?;
+  let node = Node::new(node_name, node_serial_number);
+  Ok((input, Port::new(node, id).into()))
 }
 
 fn read_reference<'input>(
   input: &'input [u8],
-  atom_cache: &AtomCache,
+  atom_references: &AtomCacheReferences,
 ) -> read::IResult<'input, Term> {
-  let (input, node_name) = read_node_name(input, atom_cache)?;
+  let (input, node_name) = read_node_name(input, atom_references)?;
   let (input, id) = read::be_u32(input)?;
   let (input, serial_number) = read::be_u8::<u32>(input)?;
-  let node = Node::new(node_name, serial_number)?;
-  Ok((input, Reference { node, id }.into()))
+  let node = Node::new(node_name, serial_number);
+  let ids = vec![id].into_boxed_slice();
+  Ok((input, Reference { node, ids }.into()))
+}
+
+fn read_new_reference<'input>(
+  input: &'input [u8],
+  creation_format: CreationFormat,
+  atom_references: &AtomCacheReferences,
+) -> read::IResult<'input, Term> {
+  let (input, len) = read::be_u16::<usize>(input)?;
+  let (input, node_name) = read_node_name(input, atom_references)?;
+  let (input, serial_number) = creation_format.read(input)?;
+
+  let mut ids = Vec::with_capacity(len);
+  let input = (0..len).try_fold(input, |input, _| -> Result<_> {
+    let (input, id) = read::be_u32::<u32>(input)?;
+    ids.push(id);
+    Ok(input)
+  })?;
+
+  let node = Node::new(node_name, serial_number);
+  let ids = ids.into_boxed_slice();
+  Ok((input, Reference { node, ids }.into()))
+}
+
+/// Reads a term that is part of a larger one, such as the module of a fun, and converts it.
+fn read_field<'input, T: FromTerm>(
+  input: &'input [u8],
+  atom_references: &AtomCacheReferences,
+) -> read::IResult<'input, T> {
+  let (input, term) = read_term(input, atom_references)?;
+  Ok((input, T::from_term(&term)?))
 }
 
 fn read_node_name<'input>(
   input: &'input [u8],
-  atom_cache: &AtomCache,
+  atom_references: &AtomCacheReferences,
 ) -> read::IResult<'input, Atom> {
-  match read_term(input, atom_cache)? {
+  match read_term(input, atom_references)? {
     (input, Term::Atom(node_name)) => Ok((input, node_name)),
     (_, term) => Err(ErrorKind::NodeIsNotAnAtom(term.kind()).into()),
   }
@@ -153,13 +521,13 @@ fn read_node_name<'input>(
 fn read_tuple<'input>(
   input: &'input [u8],
   size_format: TupleSizeFormat,
-  atom_cache: &AtomCache,
+  atom_references: &AtomCacheReferences,
 ) -> read::IResult<'input, Term> {
   let (input, len) = size_format.read(input)?;
 
   let mut elements = Vec::with_capacity(len);
   let input = (0..len).try_fold(input, |input, _| -> Result<_> {
-    let (input, element) = read_term(input, atom_cache)?;
+    let (input, element) = read_term(input, atom_references)?;
     elements.push(element);
     Ok(input)
   })?;
@@ -167,18 +535,22 @@ fn read_tuple<'input>(
   Ok((input, Tuple(elements.into_boxed_slice()).into()))
 }
 
-fn read_list<'input>(input: &'input [u8], atom_cache: &AtomCache) -> read::IResult<'input, Term> {
+fn read_list<'input>(
+  input: &'input [u8],
+  atom_references: &AtomCacheReferences,
+) -> read::IResult<'input, Term> {
   let (input, len) = read::be_u32::<u32>(input)?;
-  let len_with_tail = cast_len(len + 1)?;
+  let len = cast_len(len)?;
 
-  let mut elements = Vec::with_capacity(len_with_tail);
-  let input = (0..len_with_tail).try_fold(input, |input, _| -> Result<_> {
-    let (input, element) = read_term(input, atom_cache)?;
+  let mut elements = Vec::with_capacity(len);
+  let input = (0..len).try_fold(input, |input, _| -> Result<_> {
+    let (input, element) = read_term(input, atom_references)?;
     elements.push(element);
     Ok(input)
   })?;
+  let (input, tail) = read_term(input, atom_references)?;
 
-  Ok((input, List(elements.into_boxed_slice()).into()))
+  Ok((input, List::improper(elements, tail).into()))
 }
 
 fn read_string(input: &[u8]) -> read::IResult<Term> {
@@ -190,7 +562,7 @@ fn read_string(input: &[u8]) -> read::IResult<Term> {
     elements.push(Term::Integer(byte.into()));
   }
 
-  Ok((input, List(elements.into_boxed_slice()).into()))
+  Ok((input, List::new(elements).into()))
 }
 
 fn read_binary(input: &[u8]) -> read::IResult<Term> {
@@ -203,9 +575,1012 @@ fn read_binary(input: &[u8]) -> read::IResult<Term> {
   ))
 }
 
+fn read_bit_binary(input: &[u8]) -> read::IResult<Term> {
+  let (input, len) = read::be_u32::<u32>(input)?;
+  let len = cast_len(len)?;
+  let (input, trailing_bits) = read::be_u8::<u8>(input)?;
+  let (input, bytes) = read::take(input, len)?;
+  // Whole bytes, which encoders may write with 8 trailing bits, or 0 if there are no bytes at all.
+  let term = match (len, trailing_bits) {
+    (_, 8) | (0, 0) => Binary(bytes.to_vec().into_boxed_slice()).into(),
+    _ => BitBinary::new(bytes, trailing_bits)?.into(),
+  };
+  Ok((input, term))
+}
+
+fn read_map<'input>(
+  input: &'input [u8],
+  atom_references: &AtomCacheReferences,
+) -> read::IResult<'input, Term> {
+  let (input, len) = read::be_u32::<u32>(input)?;
+  let len = cast_len(len)?;
+
+  let mut entries = Vec::with_capacity(len);
+  let input = (0..len).try_fold(input, |input, _| -> Result<_> {
+    let (input, key) = read_term(input, atom_references)?;
+    let (input, value) = read_term(input, atom_references)?;
+    entries.push((key, value));
+    Ok(input)
+  })?;
+
+  Ok((input, Map(entries.into_boxed_slice()).into()))
+}
+
+fn read_export<'input>(
+  input: &'input [u8],
+  atom_references: &AtomCacheReferences,
+) -> read::IResult<'input, Term> {
+  let (input, module) = read_field(input, atom_references)?;
+  let (input, function) = read_field(input, atom_references)?;
+  let (input, arity) = read_field(input, atom_references)?;
+  let export = Export {
+    module,
+    function,
+    arity,
+  };
+  Ok((input, export.into()))
+}
+
+fn read_new_fun<'input>(
+  input: &'input [u8],
+  atom_references: &AtomCacheReferences,
+) -> read::IResult<'input, Term> {
+  // The size includes the 4 bytes of the size itself.
+  let encoded = input;
+  let (input, size) = read::be_u32::<u32>(input)?;
+  let size = cast_len(size)?;
+  let (rest, input) = match size.checked_sub(4) {
+    Some(size) => read::take(input, size)?,
+    None => return Err(ErrorKind::LenOutOfRange(size as u64).into()),
+  };
+  let encoded = &encoded[..size];
+
+  let (input, arity) = read::be_u8(input)?;
+  let (input, uniq) = read::take(input, 16)?;
+  let (input, index) = read::be_u32(input)?;
+  let (input, free_var_count) = read::be_u32::<u32>(input)?;
+  let free_var_count = cast_len(free_var_count)?;
+  let (input, module) = read_field(input, atom_references)?;
+  let (input, old_index) = read_field(input, atom_references)?;
+  let (input, old_uniq) = read_field(input, atom_references)?;
+  let (input, pid) = read_field(input, atom_references)?;
+  let mut free_vars = Vec::with_capacity(free_var_count.min(input.len()));
+  let input = (0..free_var_count).try_fold(input, |input, _| -> Result<_> {
+    let (input, free_var) = read_term(input, atom_references)?;
+    free_vars.push(free_var);
+    Ok(input)
+  })?;
+  if !input.is_empty() {
+    return Err(ErrorKind::TrailingBytes(input.len()).into());
+  }
+
+  let mut fun = Fun {
+    module,
+    arity,
+    uniq: Default::default(),
+    index,
+    old_index,
+    old_uniq,
+    pid,
+    free_vars: free_vars.into_boxed_slice(),
+    encoded: Some(encoded.into()),
+  };
+  fun.uniq.copy_from_slice(uniq);
+  Ok((rest, fun.into()))
+}
+
+/// Writes the outermost term of a message or of a binary, right after the version magic, compressing
+/// it if `options` asks for it.
+pub fn write_outer_term<W: io::Write>(
+  output: &mut W,
+  term: &Term,
+  options: WriteOptions,
+) -> Result<()> {
+  let level = match options.compression_level {
+    Some(level) if level > 9 => return Err(ErrorKind::CompressionLevelOutOfRange(level).into()),
+    Some(level) => level,
+    None => return write_term(output, term, options),
+  };
+
+  let mut uncompressed = Vec::new();
+  write_term(&mut uncompressed, term, options)?;
+  let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+  io::Write::write_all(&mut encoder, &uncompressed)?;
+  let compressed = encoder.finish()?;
+
+  // The tag and the size take 5 bytes.
+  if compressed.len() + 5 < uncompressed.len() {
+    write::be_u8(output, COMPRESSED)?;
+    write::be_u32(output, uncast_len(uncompressed.len())?)?;
+    write::bytes(output, &compressed)
+  } else {
+    write::bytes(output, &uncompressed)
+  }
+}
+
+pub fn write_term<W: io::Write>(output: &mut W, term: &Term, options: WriteOptions) -> Result<()> {
+  let encoder = Encoder {
+    options,
+    atom_indices: None,
+  };
+  encode(output, term, encoder)
+}
+
+/// Writes a term of a message that starts with a distribution header, replacing the atoms that the
+/// header refers to by their index in `atom_indices`.
+pub fn write_term_with_atom_cache<W: io::Write>(
+  output: &mut W,
+  term: &Term,
+  options: WriteOptions,
+  atom_indices: &HashMap<Atom, u8>,
+) -> Result<()> {
+  let encoder = Encoder {
+    options,
+    atom_indices: Some(atom_indices),
+  };
+  encode(output, term, encoder)
+}
+
+fn encode<W: io::Write>(output: &mut W, term: &Term, encoder: Encoder) -> Result<()> {
+  match order::canonical(term) {
+    Term::Nil => write::be_u8(output, ei::NIL_EXT),
+    Term::Integer(value) => write_integer(output, *value),
+    Term::BigInteger(value) => write_big_integer(output, value),
+    Term::Float(value) => match encoder.options.float_format {
+      FloatFormat::Old => write_float(output, *value),
+      FloatFormat::New => write_new_float(output, *value),
+    },
+    Term::Atom(atom) => write_atom(output, atom, encoder),
+    Term::Pid(pid) => write_pid(output, pid, encoder),
+    Term::Port(port) => write_port(output, port, encoder),
+    Term::Reference(reference) => write_reference(output, reference, encoder),
+    Term::Tuple(tuple) => write_tuple(output, tuple, encoder),
+    Term::List(list) => write_list(output, list, encoder),
+    Term::Binary(binary) => write_binary(output, binary),
+    Term::BitBinary(bit_binary) => write_bit_binary(output, bit_binary),
+    Term::Map(map) => write_map(output, map, encoder),
+    Term::Export(export) => write_export(output, export, encoder),
+    Term::Fun(fun) => write_new_fun(output, fun, encoder),
+  }
+}
+
+fn write_integer<W: io::Write>(output: &mut W, value: i32) -> Result<()> {
+  if 0 <= value && value <= u8::max_value().into() {
+    write::be_u8(output, ei::SMALL_INTEGER_EXT)?;
+    write::be_u8(output, value as u8)
+  } else {
+    write::be_u8(output, ei::INTEGER_EXT)?;
+    write::be_i32(output, value)
+  }
+}
+
+fn write_big_integer<W: io::Write>(output: &mut W, value: &BigInteger) -> Result<()> {
+  if let Some(value) = num_traits::ToPrimitive::to_i32(value) {
+    return write_integer(output, value);
+  }
+
+  let digits = value.digits();
+  if digits.len() <= u8::max_value().into() {
+    write::be_u8(output, ei::SMALL_BIG_EXT)?;
+    write::be_u8(output, digits.len() as u8)?;
+  } else {
+    write::be_u8(output, ei::LARGE_BIG_EXT)?;
+    write::be_u32(output, uncast_len(digits.len())?)?;
+  }
+  write::be_u8(output, value.is_negative() as u8)?;
+  write::bytes(output, digits)
+}
+
+fn write_float<W: io::Write>(output: &mut W, value: f64) -> Result<()> {
+  // Rust writes exponents like `e3` where C writes them like `e+03`.
+  let text = format!("{:.20e}", value);
+  let (mantissa, exponent) = text.split_at(text.find('e').unwrap());
+  let exponent = exponent[1..].parse::<i32>().unwrap();
+  let mut float_bytes = format!("{}e{:+03}", mantissa, exponent).into_bytes();
+  float_bytes.resize(OLD_FLOAT_LEN, 0);
+  write::be_u8(output, ei::FLOAT_EXT)?;
+  write::bytes(output, &float_bytes)
+}
+
+fn write_new_float<W: io::Write>(output: &mut W, value: f64) -> Result<()> {
+  write::be_u8(output, ei::NEW_FLOAT_EXT)?;
+  write::be_f64(output, value)
+}
+
+fn write_atom<W: io::Write>(output: &mut W, atom: &Atom, encoder: Encoder) -> Result<()> {
+  if let Some(&index) = encoder.atom_indices.and_then(|indices| indices.get(atom)) {
+    write::be_u8(output, ei::ATOM_CACHE_REF)?;
+    return write::be_u8(output, index);
+  }
+  match encoder.options.atom_encoding {
+    AtomEncoding::Latin1 => write_atom_latin1(output, atom),
+    AtomEncoding::Utf8 => write_atom_utf8(output, atom),
+  }
+}
+
+/// Encodes the name of an atom in Latin-1, where each character is a byte.
+pub(crate) fn latin1_bytes(atom: &Atom) -> Result<Vec<u8>> {
+  atom
+    .name()
+    .chars()
+    .map(|c| num_traits::NumCast::from(u32::from(c)))
+    .collect::<Option<Vec<u8>>>()
+    .ok_or_else(|| ErrorKind::AtomIsNotLatin1(atom.clone()).into())
+}
+
+fn write_atom_latin1<W: io::Write>(output: &mut W, atom: &Atom) -> Result<()> {
+  let atom_bytes = latin1_bytes(atom)?;
+  // Atoms have at most 255 characters, so their Latin-1 encoding always fits in a small atom.
+  write::be_u8(output, ei::SMALL_ATOM_EXT)?;
+  write::be_u8(output, atom_bytes.len() as u8)?;
+  write::bytes(output, &atom_bytes)
+}
+
+fn write_atom_utf8<W: io::Write>(output: &mut W, atom: &Atom) -> Result<()> {
+  let atom_bytes = atom.name().as_bytes();
+  if atom_bytes.len() <= u8::max_value().into() {
+    write::be_u8(output, ei::SMALL_ATOM_UTF8_EXT)?;
+    write::be_u8(output, atom_bytes.len() as u8)?;
+  } else {
+    write::be_u8(output, ei::ATOM_UTF8_EXT)?;
+    write::be_u16(output, uncast_len(atom_bytes.len())?)?;
+  }
+  write::bytes(output, atom_bytes)
+}
+
+fn write_pid<W: io::Write>(output: &mut W, pid: &Pid, encoder: Encoder) -> Result<()> {
+  const OLD_ID_MAX: u32 = (1 << 15) - 1;
+  const OLD_SERIAL_MAX: u32 = (1 << 13) - 1;
+
+  match encoder.options.creation_format {
+    CreationFormat::Old if pid.id > OLD_ID_MAX || pid.serial > OLD_SERIAL_MAX => {
+      return Err(ErrorKind::PidOutOfRange(pid.node.clone(), pid.id, pid.serial).into());
+    }
+    CreationFormat::Old => write::be_u8(output, ei::PID_EXT)?,
+    CreationFormat::New => write::be_u8(output, ei::NEW_PID_EXT)?,
+  }
+  write_atom(output, &pid.node.name, encoder)?;
+  write::be_u32(output, pid.id)?;
+  write::be_u32(output, pid.serial)?;
+  encoder.options.creation_format.write(output, &pid.node)
+}
+
+fn write_port<W: io::Write>(output: &mut W, port: &Port, encoder: Encoder) -> Result<()> {
+  const OLD_ID_MAX: u64 = (1 << 28) - 1;
+
+  let id = port.id();
+  if let CreationFormat::Old = encoder.options.creation_format {
+    if id > OLD_ID_MAX {
+      return Err(ErrorKind::PortOutOfRange(port.node.clone(), id).into());
+    }
+    write::be_u8(output, ei::PORT_EXT)?;
+    write_atom(output, &port.node.name, encoder)?;
+    write::be_u32(output, id as u32)?;
+  } else if id <= u32::max_value().into() {
+    write::be_u8(output, NEW_PORT_EXT)?;
+    write_atom(output, &port.node.name, encoder)?;
+    write::be_u32(output, id as u32)?;
+  } else {
+    write::be_u8(output, V4_PORT_EXT)?;
+    write_atom(output, &port.node.name, encoder)?;
+    write::be_u64(output, id)?;
+  }
+  encoder.options.creation_format.write(output, &port.node)
+}
+
+fn write_reference<W: io::Write>(
+  output: &mut W,
+  reference: &Reference,
+  encoder: Encoder,
+) -> Result<()> {
+  match encoder.options.creation_format {
+    CreationFormat::Old => write::be_u8(output, ei::NEW_REFERENCE_EXT)?,
+    CreationFormat::New => write::be_u8(output, NEWER_REFERENCE_EXT)?,
+  }
+  write::be_u16(output, uncast_len(reference.ids.len())?)?;
+  write_atom(output, &reference.node.name, encoder)?;
+  encoder
+    .options
+    .creation_format
+    .write(output, &reference.node)?;
+  reference
+    .ids
+    .iter()
+    .try_for_each(|&id| write::be_u32(output, id))
+}
+
+fn write_tuple<W: io::Write>(output: &mut W, tuple: &Tuple, encoder: Encoder) -> Result<()> {
+  let Tuple(elements) = tuple;
+  if elements.len() <= u8::max_value().into() {
+    write::be_u8(output, ei::SMALL_TUPLE_EXT)?;
+    write::be_u8(output, elements.len() as u8)?;
+  } else {
+    write::be_u8(output, ei::LARGE_TUPLE_EXT)?;
+    write::be_u32(output, uncast_len(elements.len())?)?;
+  }
+  elements
+    .iter()
+    .try_for_each(|element| encode(output, element, encoder))
+}
+
+fn write_list<W: io::Write>(output: &mut W, list: &List, encoder: Encoder) -> Result<()> {
+  if list.is_empty() {
+    return encode(output, list.tail(), encoder);
+  }
+
+  // Like the runtime, proper lists of bytes are written as strings, so that the lists in free
+  // variables of funs are written back with the bytes they were read from.
+  if list.is_proper() && list.len() <= u16::max_value().into() {
+    let string_bytes = list
+      .iter()
+      .map(|element| match element {
+        Term::Integer(value) => num_traits::NumCast::from(*value),
+        _ => None,
+      })
+      .collect::<Option<Vec<u8>>>();
+    if let Some(string_bytes) = string_bytes {
+      write::be_u8(output, ei::STRING_EXT)?;
+      write::be_u16(output, string_bytes.len() as u16)?;
+      return write::bytes(output, &string_bytes);
+    }
+  }
+
+  write::be_u8(output, ei::LIST_EXT)?;
+  write::be_u32(output, uncast_len(list.len())?)?;
+  list
+    .iter()
+    .try_for_each(|element| encode(output, element, encoder))?;
+  encode(output, list.tail(), encoder)
+}
+
+fn write_binary<W: io::Write>(output: &mut W, binary: &Binary) -> Result<()> {
+  let Binary(bytes) = binary;
+  write::be_u8(output, ei::BINARY_EXT)?;
+  write::be_u32(output, uncast_len(bytes.len())?)?;
+  write::bytes(output, bytes)
+}
+
+fn write_bit_binary<W: io::Write>(output: &mut W, bit_binary: &BitBinary) -> Result<()> {
+  write::be_u8(output, ei::BIT_BINARY_EXT)?;
+  write::be_u32(output, uncast_len(bit_binary.bytes.len())?)?;
+  write::be_u8(output, bit_binary.trailing_bits)?;
+  write::bytes(output, &bit_binary.bytes)
+}
+
+fn write_map<W: io::Write>(output: &mut W, map: &Map, encoder: Encoder) -> Result<()> {
+  write::be_u8(output, ei::MAP_EXT)?;
+  write::be_u32(output, uncast_len(map.len())?)?;
+  map.iter().try_for_each(|(key, value)| {
+    encode(output, key, encoder)?;
+    encode(output, value, encoder)
+  })
+}
+
+fn write_export<W: io::Write>(output: &mut W, export: &Export, encoder: Encoder) -> Result<()> {
+  write::be_u8(output, ei::EXPORT_EXT)?;
+  write_atom(output, &export.module, encoder)?;
+  write_atom(output, &export.function, encoder)?;
+  write_integer(output, export.arity.into())
+}
+
+fn write_new_fun<W: io::Write>(output: &mut W, fun: &Fun, encoder: Encoder) -> Result<()> {
+  if let Some(encoded) = fun
+    .encoded
+    .as_ref()
+    .filter(|encoded| decodes_to(encoded, fun))
+  {
+    write::be_u8(output, ei::NEW_FUN_EXT)?;
+    return write::bytes(output, encoded);
+  }
+
+  // The fun is written to a buffer first, since it starts with its size.
+  let mut fun_bytes = Vec::new();
+  write::be_u8(&mut fun_bytes, fun.arity)?;
+  write::bytes(&mut fun_bytes, &fun.uniq)?;
+  write::be_u32(&mut fun_bytes, fun.index)?;
+  write::be_u32(&mut fun_bytes, uncast_len(fun.free_vars.len())?)?;
+  write_atom(&mut fun_bytes, &fun.module, encoder)?;
+  encode(&mut fun_bytes, &u64::from(fun.old_index).into(), encoder)?;
+  encode(&mut fun_bytes, &u64::from(fun.old_uniq).into(), encoder)?;
+  write_pid(&mut fun_bytes, &fun.pid, encoder)?;
+  fun
+    .free_vars
+    .iter()
+    .try_for_each(|free_var| encode(&mut fun_bytes, free_var, encoder))?;
+
+  write::be_u8(output, ei::NEW_FUN_EXT)?;
+  write::be_u32(output, uncast_len(fun_bytes.len() + 4)?)?;
+  write::bytes(output, &fun_bytes)
+}
+
+/// Returns whether `encoded` is a `NEW_FUN_EXT` of `fun`, without atom cache references since they
+/// only apply to the message the fun was read from.
+fn decodes_to(encoded: &[u8], fun: &Fun) -> bool {
+  match read_new_fun(encoded, &AtomCacheReferences::new()) {
+    Ok(([], Term::Fun(decoded))) => {
+      decoded.module == fun.module
+        && decoded.arity == fun.arity
+        && decoded.uniq == fun.uniq
+        && decoded.index == fun.index
+        && decoded.old_index == fun.old_index
+        && decoded.old_uniq == fun.old_uniq
+        && decoded.pid == fun.pid
+        && decoded.free_vars == fun.free_vars
+    }
+    _ => false,
+  }
+}
+
 fn cast_len<From>(value: From) -> Result<usize>
 where
   From: Into<u64> + num_traits::ToPrimitive + Copy,
 {
   num_traits::NumCast::from(value).ok_or_else(|| ErrorKind::LenOutOfRange(value.into()).into())
 }
+
+pub fn uncast_len<To>(len: usize) -> Result<To>
+where
+  To: Into<u64> + num_traits::Bounded + num_traits::NumCast,
+{
+  num_traits::NumCast::from(len)
+    .ok_or_else(|| ErrorKind::EncodedLenOutOfRange(len, To::max_value().into()).into())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn atom(name: &str) -> Term {
+    Term::Atom(Atom::new(name).unwrap())
+  }
+
+  fn node(serial_number: u32) -> Node {
+    Node::new(Atom::new("peer@host").unwrap(), serial_number)
+  }
+
+  /// Checks that `term` is read back unchanged after being written, and returns the bytes it was
+  /// written as.
+  fn assert_round_trip(term: &Term) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_term(&mut bytes, term, Default::default()).unwrap();
+    let (input, read) = read_term(&bytes, &AtomCacheReferences::new()).unwrap();
+    assert!(input.is_empty(), "{:?} left {} bytes", term, input.len());
+    // Terms cannot be compared, but their debug output shows every field.
+    assert_eq!(format!("{:?}", read), format!("{:?}", term));
+    bytes
+  }
+
+  fn round_trip(bytes: &[u8]) -> Vec<u8> {
+    term_to_binary(&binary_to_term(bytes).unwrap(), Default::default()).unwrap()
+  }
+
+  /// `fun() -> {"ab", 1.5} end` from module `m`, with free variables encoded like current nodes
+  /// do.
+  fn fun_bytes(free_vars: &[&[u8]]) -> Vec<u8> {
+    let mut fun = vec![0];
+    fun.extend_from_slice(&[7; 16]);
+    fun.extend_from_slice(&[0, 0, 0, 3, 0, 0, 0, free_vars.len() as u8]);
+    fun.extend_from_slice(&[ei::SMALL_ATOM_UTF8_EXT, 1, b'm']);
+    fun.extend_from_slice(&[ei::SMALL_INTEGER_EXT, 3]);
+    fun.extend_from_slice(&[ei::INTEGER_EXT, 1, 2, 3, 4]);
+    fun.extend_from_slice(&[ei::NEW_PID_EXT, ei::SMALL_ATOM_UTF8_EXT, 13]);
+    fun.extend_from_slice(b"nonode@nohost");
+    fun.extend_from_slice(&[0, 0, 0, 80, 0, 0, 0, 0, 0, 0, 0, 0]);
+    free_vars
+      .iter()
+      .for_each(|free_var| fun.extend_from_slice(free_var));
+
+    let mut bytes = vec![131, ei::NEW_FUN_EXT];
+    bytes.extend_from_slice(&(fun.len() as u32 + 4).to_be_bytes());
+    bytes.extend_from_slice(&fun);
+    bytes
+  }
+
+  #[test]
+  fn terms_round_trip() {
+    let terms = vec![
+      atom("ok"),
+      atom("hello world"),
+      Term::Integer(0),
+      Term::Integer(255),
+      Term::Integer(256),
+      Term::Integer(-1),
+      Term::Integer(i32::min_value()),
+      Term::Integer(i32::max_value()),
+      Term::Float(1.5),
+      Term::Float(-0.0),
+      Term::Tuple(Tuple(vec![].into_boxed_slice())),
+      Term::Tuple(Tuple(vec![atom("ok"), Term::Integer(1)].into_boxed_slice())),
+      Term::Tuple(Tuple((0..300).map(Term::Integer).collect())),
+      Term::Nil,
+      Term::List(List::new(vec![atom("a"), Term::Integer(1000), Term::Nil])),
+      Term::Binary(Binary(vec![].into_boxed_slice())),
+      Term::Binary(Binary(vec![1, 2, 255].into_boxed_slice())),
+      Term::Pid(Pid::new(node(1), 42, 3)),
+      Term::Pid(Pid::new(node(1 << 20), 1 << 20, 1 << 20)),
+      Term::Reference(Reference {
+        node: node(2),
+        ids: vec![7, 8, 9].into_boxed_slice(),
+      }),
+    ];
+    for term in &terms {
+      assert_round_trip(term);
+    }
+  }
+
+  #[test]
+  fn big_integers_round_trip() {
+    let bytes = assert_round_trip(&Term::from(1i64 << 31));
+    assert_eq!(bytes[..3], [ei::SMALL_BIG_EXT, 4, 0]);
+    let bytes = assert_round_trip(&Term::from(-(1i64 << 31) - 1));
+    assert_eq!(bytes[..3], [ei::SMALL_BIG_EXT, 4, 1]);
+    assert_round_trip(&Term::from(u64::max_value()));
+    let bytes = assert_round_trip(&BigInteger::new(true, vec![7; 300]).into());
+    assert_eq!(bytes[..6], [ei::LARGE_BIG_EXT, 0, 0, 1, 44, 1]);
+
+    // Big integers that fit in an integer are written as one.
+    let mut bytes = Vec::new();
+    write_term(
+      &mut bytes,
+      &Term::BigInteger(BigInteger::new(false, vec![5])),
+      Default::default(),
+    )
+    .unwrap();
+    assert_eq!(bytes, [ei::SMALL_INTEGER_EXT, 5]);
+  }
+
+  #[test]
+  fn maps_round_trip() {
+    let bytes = assert_round_trip(&Term::Map(Map(Box::new([]))));
+    assert_eq!(bytes, [ei::MAP_EXT, 0, 0, 0, 0]);
+    let map =
+      Map(vec![(atom("a"), Term::Integer(1)), (Term::Integer(2), atom("b"))].into_boxed_slice());
+    assert_eq!(map.get(&Term::Integer(2)).unwrap(), &atom("b"));
+    assert!(map.get(&atom("b")).is_none());
+    let bytes = assert_round_trip(&Term::Map(map));
+    assert_eq!(bytes[..5], [ei::MAP_EXT, 0, 0, 0, 2]);
+  }
+
+  #[test]
+  fn references_are_read_in_every_encoding() {
+    let expected = Term::Reference(Reference {
+      node: node(2),
+      ids: vec![7, 8].into_boxed_slice(),
+    });
+    let node_name = [&[ei::SMALL_ATOM_UTF8_EXT, 9][..], b"peer@host"].concat();
+
+    let mut bytes = vec![ei::NEW_REFERENCE_EXT, 0, 2];
+    bytes.extend_from_slice(&node_name);
+    bytes.extend_from_slice(&[2, 0, 0, 0, 7, 0, 0, 0, 8]);
+    let (_, term) = read_term(&bytes, &AtomCacheReferences::new()).unwrap();
+    assert_eq!(format!("{:?}", term), format!("{:?}", expected));
+
+    let mut bytes = vec![NEWER_REFERENCE_EXT, 0, 2];
+    bytes.extend_from_slice(&node_name);
+    bytes.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 7, 0, 0, 0, 8]);
+    let (_, term) = read_term(&bytes, &AtomCacheReferences::new()).unwrap();
+    assert_eq!(format!("{:?}", term), format!("{:?}", expected));
+    // References are written in the newest encoding.
+    assert_eq!(assert_round_trip(&term), bytes);
+
+    let mut bytes = vec![ei::REFERENCE_EXT];
+    bytes.extend_from_slice(&node_name);
+    bytes.extend_from_slice(&[0, 0, 0, 7, 2]);
+    let (_, term) = read_term(&bytes, &AtomCacheReferences::new()).unwrap();
+    match term {
+      Term::Reference(reference) => assert_eq!(reference.ids[..], [7]),
+      term => panic!("{:?}", term),
+    }
+  }
+
+  #[test]
+  fn ports_round_trip() {
+    let node_name = [&[ei::SMALL_ATOM_UTF8_EXT, 9][..], b"peer@host"].concat();
+
+    let bytes = assert_round_trip(&Port::new(node(1), 7).into());
+    let mut expected = vec![NEW_PORT_EXT];
+    expected.extend_from_slice(&node_name);
+    expected.extend_from_slice(&[0, 0, 0, 7, 0, 0, 0, 1]);
+    assert_eq!(bytes, expected);
+
+    let bytes = assert_round_trip(&Port::new(node(1), 1 << 40).into());
+    let mut expected = vec![V4_PORT_EXT];
+    expected.extend_from_slice(&node_name);
+    expected.extend_from_slice(&[0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(bytes, expected);
+
+    let mut bytes = vec![ei::PORT_EXT];
+    bytes.extend_from_slice(&node_name);
+    bytes.extend_from_slice(&[0, 0, 0, 7, 1]);
+    let (_, term) = read_term(&bytes, &AtomCacheReferences::new()).unwrap();
+    assert_eq!(
+      format!("{:?}", term),
+      format!("{:?}", Term::from(Port::new(node(1), 7)))
+    );
+  }
+
+  #[test]
+  fn creations_are_written_in_the_requested_format() {
+    let old = WriteOptions {
+      creation_format: CreationFormat::Old,
+      ..Default::default()
+    };
+    let node_name = [&[ei::SMALL_ATOM_UTF8_EXT, 9][..], b"peer@host"].concat();
+    let pid = Term::Pid(Pid::new(node(3), 42, 7));
+
+    let mut bytes = Vec::new();
+    write_term(&mut bytes, &pid, old).unwrap();
+    let mut expected = vec![ei::PID_EXT];
+    expected.extend_from_slice(&node_name);
+    expected.extend_from_slice(&[0, 0, 0, 42, 0, 0, 0, 7, 3]);
+    assert_eq!(bytes, expected);
+    let (_, read) = read_term(&bytes, &AtomCacheReferences::new()).unwrap();
+    assert_eq!(format!("{:?}", read), format!("{:?}", pid));
+
+    let bytes = assert_round_trip(&pid);
+    let mut expected = vec![ei::NEW_PID_EXT];
+    expected.extend_from_slice(&node_name);
+    expected.extend_from_slice(&[0, 0, 0, 42, 0, 0, 0, 7, 0, 0, 0, 3]);
+    assert_eq!(bytes, expected);
+
+    // Legacy peers only support 2-bit creations, 15-bit pid ids, 13-bit pid serials and 28-bit
+    // port ids.
+    let out_of_range = vec![
+      Term::Pid(Pid::new(node(4), 42, 7)),
+      Term::Pid(Pid::new(node(3), 1 << 15, 7)),
+      Term::Pid(Pid::new(node(3), 42, 1 << 13)),
+      Term::Port(Port::new(node(3), 1 << 28)),
+      Term::Reference(Reference {
+        node: node(4),
+        ids: vec![1].into_boxed_slice(),
+      }),
+    ];
+    for term in &out_of_range {
+      assert!(
+        write_term(&mut Vec::new(), term, old).is_err(),
+        "{:?}",
+        term
+      );
+      assert_round_trip(term);
+    }
+  }
+
+  #[test]
+  fn improper_lists_round_trip() {
+    let list = List::improper(vec![atom("a"), Term::Integer(1)], atom("b"));
+    assert!(!list.is_proper());
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.tail(), &atom("b"));
+    let bytes = assert_round_trip(&list.into());
+    assert_eq!(bytes[..5], [ei::LIST_EXT, 0, 0, 0, 2]);
+    assert_eq!(bytes[bytes.len() - 3..], [ei::SMALL_ATOM_UTF8_EXT, 1, b'b']);
+
+    // A list without elements is its tail.
+    let mut bytes = Vec::new();
+    let empty = List::improper(vec![], atom("b"));
+    write_term(&mut bytes, &empty.into(), Default::default()).unwrap();
+    assert_eq!(bytes, [ei::SMALL_ATOM_UTF8_EXT, 1, b'b']);
+
+    let (_, term) = read_term(
+      &[ei::STRING_EXT, 0, 2, b'h', b'i'],
+      &AtomCacheReferences::new(),
+    )
+    .unwrap();
+    match term {
+      Term::List(list) => {
+        assert!(list.is_proper());
+        assert_eq!(list.elements[..], [Term::Integer(104), Term::Integer(105)]);
+      }
+      term => panic!("{:?}", term),
+    }
+  }
+
+  #[test]
+  fn compressed_terms_round_trip() {
+    let term = Term::List(List::new(vec![atom("repeated"); 100]));
+    let options = WriteOptions {
+      compression_level: Some(6),
+      ..Default::default()
+    };
+    let mut bytes = Vec::new();
+    write_outer_term(&mut bytes, &term, options).unwrap();
+    assert_eq!(bytes[..5], [COMPRESSED, 0, 0, 3, 238]);
+    assert!(bytes.len() < 100);
+    let (input, read) =
+      read_outer_term(&bytes, &AtomCacheReferences::new(), Default::default()).unwrap();
+    assert!(input.is_empty());
+    assert_eq!(read, term);
+
+    // Terms that compression does not shrink are written as they are.
+    let mut bytes = Vec::new();
+    write_outer_term(&mut bytes, &atom("ok"), options).unwrap();
+    assert_eq!(bytes, [ei::SMALL_ATOM_UTF8_EXT, 2, b'o', b'k']);
+
+    let options = WriteOptions {
+      compression_level: Some(10),
+      ..Default::default()
+    };
+    assert!(write_outer_term(&mut Vec::new(), &term, options).is_err());
+  }
+
+  #[test]
+  fn uncompressed_sizes_are_checked() {
+    let term = Term::List(List::new(vec![atom("repeated"); 100]));
+    let options = WriteOptions {
+      compression_level: Some(6),
+      ..Default::default()
+    };
+    let mut bytes = Vec::new();
+    write_outer_term(&mut bytes, &term, options).unwrap();
+
+    let options = ReadOptions {
+      max_uncompressed_size: 1005,
+      ..Default::default()
+    };
+    match read_outer_term(&bytes, &AtomCacheReferences::new(), options) {
+      Err(Error(ErrorKind::UncompressedSizeOutOfRange(1006, 1005), _)) => (),
+      result => panic!("{:?}", result),
+    }
+
+    // The announced size must be the actual size.
+    bytes[4] -= 1;
+    match read_outer_term(&bytes, &AtomCacheReferences::new(), Default::default()) {
+      Err(Error(ErrorKind::UncompressedSizeMismatch(1005, 1006), _)) => (),
+      result => panic!("{:?}", result),
+    }
+  }
+
+  #[test]
+  fn binaries_decode_to_terms() {
+    let term: Term = "{ok, [1, 2], <<\"bin\">>}".parse().unwrap();
+    let bytes = term_to_binary(&term, Default::default()).unwrap();
+    assert_eq!(bytes[..3], [ei::VERSION_MAGIC, ei::SMALL_TUPLE_EXT, 3]);
+    assert_eq!(binary_to_term(&bytes).unwrap(), term);
+
+    let options = WriteOptions {
+      compression_level: Some(9),
+      ..Default::default()
+    };
+    let long: Term = List::new(vec![Term::Integer(1000); 100]).into();
+    let bytes = term_to_binary(&long, options).unwrap();
+    assert_eq!(bytes[..2], [ei::VERSION_MAGIC, COMPRESSED]);
+    assert_eq!(binary_to_term(&bytes).unwrap(), long);
+
+    assert!(binary_to_term(&[ei::SMALL_INTEGER_EXT, 1]).is_err());
+    match binary_to_term(&[ei::VERSION_MAGIC, ei::SMALL_INTEGER_EXT, 1, 2]) {
+      Err(Error(ErrorKind::TrailingBytes(1), _)) => (),
+      result => panic!("{:?}", result),
+    }
+  }
+
+  #[test]
+  fn unknown_atoms_are_rejected() {
+    let known_atoms = ["ok", "error", "peer@host"]
+      .iter()
+      .map(|name| Atom::new(*name).unwrap())
+      .collect::<HashSet<_>>();
+    let options = ReadOptions {
+      known_atoms: Some(&known_atoms),
+      ..Default::default()
+    };
+
+    let known = "{ok, [error | ok], #{ok => <<>>}}".parse::<Term>().unwrap();
+    let bytes = term_to_binary(&known, Default::default()).unwrap();
+    assert_eq!(binary_to_term_with_options(&bytes, options).unwrap(), known);
+    let pid = Pid::new(node(1), 1, 0).into();
+    let bytes = term_to_binary(&pid, Default::default()).unwrap();
+    assert_eq!(binary_to_term_with_options(&bytes, options).unwrap(), pid);
+
+    for text in &["unknown", "{ok, [1 | unknown]}", "#{ok => unknown}"] {
+      let term = text.parse::<Term>().unwrap();
+      let bytes = term_to_binary(&term, Default::default()).unwrap();
+      match binary_to_term_with_options(&bytes, options) {
+        Err(Error(ErrorKind::UnknownAtom(atom), _)) => assert_eq!(atom.name(), "unknown"),
+        result => panic!("{}: {:?}", text, result),
+      }
+    }
+  }
+
+  #[test]
+  fn old_floats_round_trip() {
+    let options = WriteOptions {
+      float_format: FloatFormat::Old,
+      ..Default::default()
+    };
+    for &value in &[1.5, -0.0, 1e300, -2.5e-300, 0.1] {
+      let mut bytes = Vec::new();
+      write_term(&mut bytes, &Term::Float(value), options).unwrap();
+      assert_eq!(bytes.len(), 32);
+      assert_eq!(bytes[0], ei::FLOAT_EXT);
+      let (_, term) = read_term(&bytes, &AtomCacheReferences::new()).unwrap();
+      assert_eq!(term, Term::Float(value));
+    }
+
+    let mut bytes = Vec::new();
+    write_term(&mut bytes, &Term::Float(1.5), options).unwrap();
+    assert_eq!(&bytes[1..27], b"1.50000000000000000000e+00");
+    assert!(bytes[27..].iter().all(|&byte| byte == 0));
+
+    let mut bytes = vec![ei::FLOAT_EXT];
+    bytes.extend_from_slice(b"not a float");
+    bytes.resize(32, 0);
+    match read_term(&bytes, &AtomCacheReferences::new()) {
+      Err(Error(ErrorKind::InvalidFloat(text), _)) => assert_eq!(text, "not a float"),
+      result => panic!("{:?}", result),
+    }
+  }
+
+  #[test]
+  fn latin1_atoms_round_trip() {
+    let options = WriteOptions {
+      atom_encoding: AtomEncoding::Latin1,
+      ..Default::default()
+    };
+    let mut bytes = Vec::new();
+    write_term(&mut bytes, &atom("café"), options).unwrap();
+    assert_eq!(bytes, [ei::SMALL_ATOM_EXT, 4, b'c', b'a', b'f', 0xe9]);
+    let (_, term) = read_term(&bytes, &AtomCacheReferences::new()).unwrap();
+    assert_eq!(term, atom("café"));
+
+    let (_, term) = read_term(
+      &[ei::ATOM_EXT, 0, 2, b'o', 0xff],
+      &AtomCacheReferences::new(),
+    )
+    .unwrap();
+    assert_eq!(term, atom("oÿ"));
+
+    // Node names follow the atom encoding too.
+    let mut bytes = Vec::new();
+    write_term(&mut bytes, &Pid::new(node(1), 1, 0).into(), options).unwrap();
+    assert_eq!(bytes[..3], [ei::NEW_PID_EXT, ei::SMALL_ATOM_EXT, 9]);
+
+    match write_term(&mut Vec::new(), &atom("привет"), options) {
+      Err(Error(ErrorKind::AtomIsNotLatin1(atom), _)) => assert_eq!(atom.name(), "привет"),
+      result => panic!("{:?}", result),
+    }
+  }
+
+  #[test]
+  fn bitstrings_round_trip() {
+    let bits = BitBinary::new(vec![1, 0xff], 3).unwrap();
+    assert_eq!(bits.bytes(), [1, 0xe0]);
+    assert_eq!(bits.bit_len(), 11);
+    let bytes = assert_round_trip(&bits.into());
+    assert_eq!(bytes, [ei::BIT_BINARY_EXT, 0, 0, 0, 2, 3, 1, 0xe0]);
+
+    // Whole bytes are binaries.
+    let (_, term) = read_term(
+      &[ei::BIT_BINARY_EXT, 0, 0, 0, 1, 8, 7],
+      &AtomCacheReferences::new(),
+    )
+    .unwrap();
+    assert_eq!(term, Binary(Box::new([7])).into());
+    let (_, term) = read_term(
+      &[ei::BIT_BINARY_EXT, 0, 0, 0, 0, 0],
+      &AtomCacheReferences::new(),
+    )
+    .unwrap();
+    assert_eq!(term, Binary(Box::new([])).into());
+
+    assert!(BitBinary::new(vec![], 3).is_err());
+    assert!(BitBinary::new(vec![1], 0).is_err());
+    assert!(read_term(
+      &[ei::BIT_BINARY_EXT, 0, 0, 0, 1, 9, 7],
+      &AtomCacheReferences::new()
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn byte_lists_are_written_as_strings() {
+    let bytes = term_to_binary(&"[97, 0, 255]".parse().unwrap(), Default::default()).unwrap();
+    assert_eq!(bytes, [131, ei::STRING_EXT, 0, 3, 97, 0, 255]);
+    assert_eq!(round_trip(&bytes), bytes);
+
+    let bytes = term_to_binary(&"[97, 256]".parse().unwrap(), Default::default()).unwrap();
+    assert_eq!(bytes[1], ei::LIST_EXT);
+    let bytes = term_to_binary(&"[97 | 98]".parse().unwrap(), Default::default()).unwrap();
+    assert_eq!(bytes[1], ei::LIST_EXT);
+  }
+
+  #[test]
+  fn funs_round_trip_with_old_encodings() {
+    let fun = Fun {
+      module: Atom::new("m").unwrap(),
+      arity: 2,
+      uniq: [7; 16],
+      index: 3,
+      old_index: 3,
+      old_uniq: 0x0102_0304,
+      pid: Pid::new(node(2), 80, 0),
+      free_vars: vec![atom("é"), Term::Float(1.5), Term::Integer(1)].into_boxed_slice(),
+      encoded: None,
+    };
+    let export = Export {
+      module: Atom::new("m").unwrap(),
+      function: Atom::new("f").unwrap(),
+      arity: 2,
+    };
+    let old = WriteOptions {
+      creation_format: CreationFormat::Old,
+      atom_encoding: AtomEncoding::Latin1,
+      float_format: FloatFormat::Old,
+      ..Default::default()
+    };
+    for term in &[Term::from(fun), export.into()] {
+      for &options in &[old, Default::default()] {
+        let mut bytes = Vec::new();
+        write_term(&mut bytes, term, options).unwrap();
+        let (input, read) = read_term(&bytes, &AtomCacheReferences::new()).unwrap();
+        assert!(input.is_empty());
+        assert_eq!(&read, term);
+      }
+    }
+  }
+
+  #[test]
+  fn funs_are_written_back_unchanged() {
+    let mut float = vec![ei::NEW_FLOAT_EXT];
+    float.extend_from_slice(&1.5f64.to_bits().to_be_bytes());
+    let bytes = fun_bytes(&[&[ei::STRING_EXT, 0, 2, b'a', b'b'], &float]);
+    assert_eq!(round_trip(&bytes), bytes);
+
+    // Encodings that the fun would not be written with by default.
+    let mut old_float = vec![ei::FLOAT_EXT];
+    old_float.extend_from_slice(&[b'0'; 31]);
+    let bytes = fun_bytes(&[
+      &[
+        ei::LIST_EXT,
+        0,
+        0,
+        0,
+        1,
+        ei::SMALL_INTEGER_EXT,
+        b'a',
+        ei::NIL_EXT,
+      ],
+      &[ei::ATOM_EXT, 0, 1, 0xe9],
+      &old_float,
+    ]);
+    assert_eq!(round_trip(&bytes), bytes);
+
+    // A fun that was changed after it was read is encoded again.
+    let mut fun = match binary_to_term(&bytes).unwrap() {
+      Term::Fun(fun) => fun,
+      term => panic!("{:?}", term),
+    };
+    fun.free_vars[0] = Term::Integer(1);
+    let term = Term::from(fun);
+    let written = term_to_binary(&term, Default::default()).unwrap();
+    assert_ne!(written, bytes);
+    assert_eq!(binary_to_term(&written).unwrap(), term);
+
+    let bytes = [131, ei::EXPORT_EXT, ei::SMALL_ATOM_UTF8_EXT, 1, b'm'];
+    let mut bytes = bytes.to_vec();
+    bytes.extend_from_slice(&[ei::SMALL_ATOM_UTF8_EXT, 1, b'f', ei::SMALL_INTEGER_EXT, 2]);
+    assert_eq!(round_trip(&bytes), bytes);
+  }
+
+  #[test]
+  fn atom_cache_references_are_resolved() {
+    let references =
+      AtomCacheReferences(vec![Atom::new("ok").unwrap(), Atom::new("error").unwrap()]);
+    let (_, term) = read_term(&[ei::ATOM_CACHE_REF, 1], &references).unwrap();
+    assert_eq!(term, atom("error"));
+
+    match read_term(&[ei::ATOM_CACHE_REF, 2], &references) {
+      Err(Error(ErrorKind::AtomCacheReferenceOutOfRange(2, 2), _)) => (),
+      result => panic!("{:?}", result),
+    }
+  }
+
+  #[test]
+  fn funs_with_atom_cache_references_are_encoded_again() {
+    let cached = fun_bytes(&[&[ei::ATOM_CACHE_REF, 0]]);
+    let references = AtomCacheReferences(vec![Atom::new("a").unwrap()]);
+    let (_, term) = read_term(&cached[1..], &references).unwrap();
+    let mut written = vec![131];
+    write_term(&mut written, &term, Default::default()).unwrap();
+    assert_eq!(written, fun_bytes(&[&[ei::SMALL_ATOM_UTF8_EXT, 1, b'a']]));
+  }
+}
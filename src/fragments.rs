@@ -0,0 +1,221 @@
+use crate::{err::*, ty::*};
+use std::collections;
+
+impl Fragments {
+  /// Creates an empty set of fragments, which refuses fragments once the messages being received
+  /// would take more than `max_len` bytes.
+  pub fn new(max_len: usize) -> Self {
+    Fragments {
+      sequences: collections::HashMap::new(),
+      dropped_sequences: collections::HashSet::new(),
+      len: 0,
+      max_len,
+    }
+  }
+
+  pub fn set_max_len(&mut self, max_len: usize) {
+    self.max_len = max_len;
+  }
+
+  /// Starts the message `sequence_id` with its first fragment, of `fragment_count` fragments.
+  /// Returns the whole message if this is its only fragment.
+  pub fn start(
+    &mut self,
+    sequence_id: u64,
+    fragment_count: u64,
+    atom_references: AtomCacheReferences,
+    bytes: &[u8],
+  ) -> Result<Option<(AtomCacheReferences, Vec<u8>)>> {
+    if fragment_count == 0 {
+      return Err(ErrorKind::UnexpectedFragment(sequence_id, 1, 0).into());
+    }
+    if self.sequences.contains_key(&sequence_id) {
+      return Err(ErrorKind::DuplicateFragmentSequence(sequence_id).into());
+    }
+    self.dropped_sequences.remove(&sequence_id);
+    self.sequences.insert(
+      sequence_id,
+      FragmentSequence {
+        atom_references,
+        next_fragment_id: fragment_count,
+        bytes: Vec::new(),
+      },
+    );
+    self.append(sequence_id, fragment_count, bytes)
+  }
+
+  /// Adds the fragment `fragment_id` to the message `sequence_id`. Fragments are numbered down to
+  /// 1, which is the last one. Returns the whole message after its last fragment.
+  ///
+  /// The message is dropped if the fragment is invalid or does not fit in memory, in which case its
+  /// remaining fragments are discarded without errors.
+  pub fn append(
+    &mut self,
+    sequence_id: u64,
+    fragment_id: u64,
+    bytes: &[u8],
+  ) -> Result<Option<(AtomCacheReferences, Vec<u8>)>> {
+    let sequence = match self.sequences.get_mut(&sequence_id) {
+      Some(sequence) => sequence,
+      None if self.dropped_sequences.contains(&sequence_id) => {
+        if fragment_id == 1 {
+          self.dropped_sequences.remove(&sequence_id);
+        }
+        return Ok(None);
+      }
+      None => return Err(ErrorKind::UnknownFragmentSequence(sequence_id).into()),
+    };
+    let expected_fragment_id = sequence.next_fragment_id;
+    let len = self.len + bytes.len();
+    if fragment_id != expected_fragment_id || len > self.max_len {
+      self.remove(sequence_id);
+      if fragment_id > 1 {
+        self.dropped_sequences.insert(sequence_id);
+      }
+      return Err(if fragment_id != expected_fragment_id {
+        ErrorKind::UnexpectedFragment(sequence_id, expected_fragment_id, fragment_id).into()
+      } else {
+        ErrorKind::FragmentsTooLarge(len, self.max_len).into()
+      });
+    }
+
+    sequence.bytes.extend_from_slice(bytes);
+    sequence.next_fragment_id -= 1;
+    self.len = len;
+    if fragment_id > 1 {
+      return Ok(None);
+    }
+    Ok(
+      self
+        .remove(sequence_id)
+        .map(|sequence| (sequence.atom_references, sequence.bytes)),
+    )
+  }
+
+  fn remove(&mut self, sequence_id: u64) -> Option<FragmentSequence> {
+    let sequence = self.sequences.remove(&sequence_id)?;
+    self.len -= sequence.bytes.len();
+    Some(sequence)
+  }
+}
+
+impl Fragmenter {
+  /// Creates a fragmenter that splits messages into fragments of at most `max_fragment_size`
+  /// bytes, not counting their headers.
+  pub fn new(max_fragment_size: usize) -> Self {
+    Fragmenter {
+      max_fragment_size: max_fragment_size.max(1),
+      next_sequence_id: 0,
+      pending: collections::VecDeque::new(),
+    }
+  }
+
+  pub fn set_max_fragment_size(&mut self, max_fragment_size: usize) {
+    self.max_fragment_size = max_fragment_size.max(1);
+  }
+
+  /// Returns whether fragments of messages are left to send.
+  pub fn has_pending_fragments(&self) -> bool {
+    !self.pending.is_empty()
+  }
+
+  /// Adds the packets of the fragments of a message that are left to send.
+  pub(crate) fn push(&mut self, packets: collections::VecDeque<Vec<u8>>) {
+    if !packets.is_empty() {
+      self.pending.push_back(packets);
+    }
+  }
+
+  /// Takes the packet of the next fragment to send. The messages being sent take turns, so that
+  /// each one gets a fragment through in turn.
+  pub(crate) fn pop(&mut self) -> Option<Vec<u8>> {
+    let mut packets = self.pending.pop_front()?;
+    let packet = packets.pop_front();
+    self.push(packets);
+    packet
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn is_whole(
+    result: Result<Option<(AtomCacheReferences, Vec<u8>)>>,
+    expected_bytes: &[u8],
+  ) -> bool {
+    match result {
+      Ok(Some((_, bytes))) => bytes == expected_bytes,
+      _ => false,
+    }
+  }
+
+  #[test]
+  fn fragments_are_reassembled() {
+    let mut fragments = Fragments::new(16);
+    let references = AtomCacheReferences::new();
+    assert!(is_whole(
+      fragments.start(1, 1, references.clone(), b"a"),
+      b"a"
+    ));
+    assert!(fragments
+      .start(2, 3, references.clone(), b"a")
+      .unwrap()
+      .is_none());
+    assert!(fragments.start(3, 2, references, b"x").unwrap().is_none());
+    assert!(fragments.append(2, 2, b"b").unwrap().is_none());
+    assert!(is_whole(fragments.append(3, 1, b"y"), b"xy"));
+    assert!(is_whole(fragments.append(2, 1, b"c"), b"abc"));
+    assert_eq!(fragments.len, 0);
+  }
+
+  #[test]
+  fn invalid_fragments_drop_their_message() {
+    let mut fragments = Fragments::new(4);
+    let references = AtomCacheReferences::new();
+    fragments.start(1, 3, references.clone(), b"a").unwrap();
+    match fragments.append(1, 1, b"c") {
+      Err(Error(ErrorKind::UnexpectedFragment(1, 2, 1), _)) => (),
+      result => panic!("{:?}", result),
+    }
+    assert!(fragments.append(1, 2, b"b").is_err());
+
+    fragments.start(2, 2, references, b"abc").unwrap();
+    match fragments.append(2, 1, b"de") {
+      Err(Error(ErrorKind::FragmentsTooLarge(5, 4), _)) => (),
+      result => panic!("{:?}", result),
+    }
+    assert_eq!(fragments.len, 0);
+  }
+
+  #[test]
+  fn dropped_messages_discard_their_remaining_fragments() {
+    let mut fragments = Fragments::new(4);
+    let references = AtomCacheReferences::new();
+    fragments.start(1, 4, references.clone(), b"a").unwrap();
+    assert!(fragments.append(1, 2, b"c").is_err());
+    assert!(fragments.append(1, 1, b"d").unwrap().is_none());
+    assert!(fragments.append(1, 1, b"d").is_err());
+
+    fragments.start(2, 3, references.clone(), b"abc").unwrap();
+    assert!(fragments.append(2, 2, b"de").is_err());
+    assert_eq!(fragments.len, 0);
+    assert!(fragments.append(2, 1, b"f").unwrap().is_none());
+    assert!(is_whole(fragments.start(2, 1, references, b"g"), b"g"));
+  }
+
+  #[test]
+  fn pending_fragments_take_turns() {
+    let mut fragmenter = Fragmenter::new(1);
+    let packets = |bytes: &[u8]| bytes.iter().map(|&byte| vec![byte]).collect();
+    fragmenter.push(packets(b"abc"));
+    fragmenter.push(packets(b"x"));
+    fragmenter.push(packets(b"yz"));
+    let mut sent = Vec::new();
+    while let Some(packet) = fragmenter.pop() {
+      sent.extend(packet);
+    }
+    assert_eq!(sent, b"axybzc");
+    assert!(!fragmenter.has_pending_fragments());
+  }
+}
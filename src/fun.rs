@@ -0,0 +1,25 @@
+use crate::ty::*;
+use std::{fmt, result};
+
+/// Formats the function like Erlang does, as in `fun lists:map/2`.
+impl fmt::Display for Export {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    write!(
+      formatter,
+      "fun {}:{}/{}",
+      self.module, self.function, self.arity
+    )
+  }
+}
+
+/// Formats the fun like Erlang does, with its module, its index and its hash, as in
+/// `#Fun<erl_eval.6.80484245>`.
+impl fmt::Display for Fun {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    write!(
+      formatter,
+      "#Fun<{}.{}.{}>",
+      self.module, self.old_index, self.old_uniq
+    )
+  }
+}
@@ -0,0 +1,664 @@
+//! Ports of the runtime's `make_hash2` and `make_hash`, which back `erlang:phash2/2` and
+//! `erlang:phash/2`.
+
+use crate::{err::*, order::canonical, ty::*};
+use num_traits::ToPrimitive;
+
+/// The golden ratio, and its multiples modulo 2^32.
+const HCONST: u32 = 0x9e37_79b9;
+const HCONST_2: u32 = 0x3c6e_f372;
+const HCONST_3: u32 = 0xdaa6_6d2b;
+const HCONST_4: u32 = 0x78dd_e6e4;
+const HCONST_5: u32 = 0x1715_609d;
+const HCONST_6: u32 = 0xb54c_da56;
+const HCONST_7: u32 = 0x5384_540f;
+const HCONST_9: u32 = 0x8ff3_4781;
+const HCONST_10: u32 = 0x2e2a_c13a;
+const HCONST_11: u32 = 0xcc62_3af3;
+const HCONST_12: u32 = 0x6a99_b4ac;
+const HCONST_13: u32 = 0x08d1_2e65;
+const HCONST_14: u32 = 0xa708_a81e;
+const HCONST_15: u32 = 0x4540_21d7;
+const HCONST_16: u32 = 0xe377_9b90;
+const HCONST_19: u32 = 0xbe1e_08bb;
+
+/// The hash of `[]` when it is the first term hashed.
+const NIL_HASH: u32 = 3_468_870_702;
+
+/// The runtime's tag value for `[]`, so that `NIL_HASH` is its hash from 0.
+const NIL_DEF: u32 = 2;
+
+const FUNNY_NUMBER1: u32 = 268_440_163;
+const FUNNY_NUMBER2: u32 = 268_439_161;
+const FUNNY_NUMBER3: u32 = 268_435_459;
+const FUNNY_NUMBER4: u32 = 268_436_141;
+const FUNNY_NUMBER5: u32 = 268_438_633;
+const FUNNY_NUMBER6: u32 = 268_437_017;
+const FUNNY_NUMBER8: u32 = 268_437_511;
+const FUNNY_NUMBER9: u32 = 268_439_627;
+const FUNNY_NUMBER10: u32 = 268_440_479;
+const FUNNY_NUMBER11: u32 = 268_440_577;
+const FUNNY_NUMBER12: u32 = 268_440_581;
+const FUNNY_NUMBER13: u32 = 268_440_593;
+const FUNNY_NUMBER14: u32 = 268_440_611;
+
+/// The range `erlang:phash2/1` uses.
+const PHASH2_DEFAULT_MASK: u32 = (1 << 27) - 1;
+
+/// The largest range accepted by `phash` and `phash2`.
+const MAX_RANGE: u64 = 1 << 32;
+
+/// Hashes a term like `erlang:phash2/2`, returning a value in `0..range`.
+///
+/// `range` must be between 1 and 2^32.
+pub fn phash2(term: &Term, range: u64) -> Result<u32> {
+  check_range(range)?;
+  let hash = make_hash2(term);
+  if range == MAX_RANGE {
+    Ok(hash)
+  } else {
+    Ok(hash % range as u32)
+  }
+}
+
+/// Hashes a term like `erlang:phash2/1`, returning a value in `0..2^27`.
+pub fn phash2_default(term: &Term) -> u32 {
+  make_hash2(term) & PHASH2_DEFAULT_MASK
+}
+
+/// Hashes a term like the deprecated `erlang:phash/2`, returning a value in `1..=range`.
+///
+/// `range` must be between 1 and 2^32.
+pub fn phash(term: &Term, range: u64) -> Result<u64> {
+  check_range(range)?;
+  let mut hash = 0;
+  make_hash(term, &mut hash);
+  Ok(1 + u64::from(hash) % range)
+}
+
+fn check_range(range: u64) -> Result<()> {
+  if range == 0 || range > MAX_RANGE {
+    return Err(ErrorKind::HashRangeOutOfRange(range).into());
+  }
+  Ok(())
+}
+
+/// Bob Jenkins' mix of three words.
+fn mix(a: &mut u32, b: &mut u32, c: &mut u32) {
+  *a = a.wrapping_sub(*b).wrapping_sub(*c) ^ (*c >> 13);
+  *b = b.wrapping_sub(*c).wrapping_sub(*a) ^ (*a << 8);
+  *c = c.wrapping_sub(*a).wrapping_sub(*b) ^ (*b >> 13);
+  *a = a.wrapping_sub(*b).wrapping_sub(*c) ^ (*c >> 12);
+  *b = b.wrapping_sub(*c).wrapping_sub(*a) ^ (*a << 16);
+  *c = c.wrapping_sub(*a).wrapping_sub(*b) ^ (*b >> 5);
+  *a = a.wrapping_sub(*b).wrapping_sub(*c) ^ (*c >> 3);
+  *b = b.wrapping_sub(*c).wrapping_sub(*a) ^ (*a << 10);
+  *c = c.wrapping_sub(*a).wrapping_sub(*b) ^ (*b >> 15);
+}
+
+fn uint32_hash_2(hash: &mut u32, first: u32, second: u32, constant: u32) {
+  let mut a = constant.wrapping_add(first);
+  let mut b = constant.wrapping_add(second);
+  mix(&mut a, &mut b, hash);
+}
+
+fn uint32_hash(hash: &mut u32, value: u32, constant: u32) {
+  uint32_hash_2(hash, value, 0, constant)
+}
+
+/// Bob Jenkins' hash of a block of bytes.
+fn block_hash(bytes: &[u8], initial: u32) -> u32 {
+  let word = |bytes: &[u8]| {
+    bytes.iter().enumerate().fold(0u32, |word, (index, &byte)| {
+      word.wrapping_add(u32::from(byte) << (8 * index))
+    })
+  };
+
+  let mut a = HCONST;
+  let mut b = HCONST;
+  let mut c = initial;
+  let mut chunks = bytes.chunks_exact(12);
+  for chunk in &mut chunks {
+    a = a.wrapping_add(word(&chunk[0..4]));
+    b = b.wrapping_add(word(&chunk[4..8]));
+    c = c.wrapping_add(word(&chunk[8..12]));
+    mix(&mut a, &mut b, &mut c);
+  }
+
+  // The first byte of `c` is reserved for the length.
+  let rest = chunks.remainder();
+  c = c.wrapping_add(bytes.len() as u32);
+  a = a.wrapping_add(word(&rest[..rest.len().min(4)]));
+  if rest.len() > 4 {
+    b = b.wrapping_add(word(&rest[4..rest.len().min(8)]));
+  }
+  if rest.len() > 8 {
+    c = c.wrapping_add(word(&rest[8..]) << 8);
+  }
+  mix(&mut a, &mut b, &mut c);
+  c
+}
+
+/// Hashes an atom name like the runtime's atom table, which hashes code points from U+0080 to
+/// U+00FF as their Latin-1 byte.
+pub(crate) fn atom_hash(atom: &Atom) -> u32 {
+  let mut hash = 0u32;
+  let mut bytes = atom.name().bytes().peekable();
+  while let Some(mut byte) = bytes.next() {
+    if byte & 0xfe == 0xc2 {
+      if let Some(&next) = bytes.peek().filter(|&&next| next & 0xc0 == 0x80) {
+        byte = (byte << 6) | (next & 0x3f);
+        bytes.next();
+      }
+    }
+    hash = (hash << 4).wrapping_add(u32::from(byte));
+    let high = hash & 0xf000_0000;
+    if high != 0 {
+      hash ^= high >> 24;
+      hash ^= high;
+    }
+  }
+  hash
+}
+
+/// Returns the sign and the magnitude of an integer term, as the 64-bit words of a bignum.
+fn integer_words(term: &Term) -> (bool, Vec<u64>) {
+  let big_integer = match term {
+    Term::Integer(value) => BigInteger::from(i64::from(*value)),
+    Term::BigInteger(value) => value.clone(),
+    _ => unreachable!(),
+  };
+  let mut words = big_integer
+    .digits()
+    .chunks(8)
+    .map(|chunk| {
+      chunk
+        .iter()
+        .rev()
+        .fold(0u64, |word, &byte| (word << 8) | u64::from(byte))
+    })
+    .collect::<Vec<_>>();
+  if words.is_empty() {
+    words.push(0);
+  }
+  (big_integer.is_negative(), words)
+}
+
+fn small_integer(term: &Term) -> Option<i64> {
+  match term {
+    Term::Integer(value) => Some(i64::from(*value)),
+    Term::BigInteger(value) => value.to_i64(),
+    _ => None,
+  }
+}
+
+fn make_hash2(term: &Term) -> u32 {
+  let mut hash = 0;
+  make_hash2_into(term, &mut hash);
+  hash
+}
+
+/// Mixes `term` into `hash`, which holds the hash of the terms before it.
+fn make_hash2_into(term: &Term, hash: &mut u32) {
+  match canonical(term) {
+    Term::Nil => {
+      if *hash == 0 {
+        *hash = NIL_HASH;
+      } else {
+        uint32_hash(hash, NIL_DEF, HCONST_2);
+      }
+    }
+    Term::Atom(atom) => {
+      if *hash == 0 {
+        *hash = atom_hash(atom);
+      } else {
+        uint32_hash(hash, atom_hash(atom), HCONST_3);
+      }
+    }
+    Term::Integer(_) | Term::BigInteger(_) => match small_integer(term) {
+      // Integers that fit in 28 bits are hashed as 32-bit integers, others as bignums.
+      Some(value) if (-(1 << 27)..(1 << 27)).contains(&value) => {
+        let value = value as i32;
+        if value < 0 {
+          uint32_hash(hash, value.wrapping_neg() as u32, HCONST);
+        }
+        uint32_hash(hash, value as u32, HCONST);
+      }
+      _ => {
+        let (is_negative, words) = integer_words(term);
+        let constant = if is_negative { HCONST_10 } else { HCONST_11 };
+        for word in words {
+          uint32_hash_2(hash, word as u32, (word >> 32) as u32, constant);
+        }
+      }
+    },
+    Term::Float(value) => {
+      // -0.0 hashes like 0.0.
+      let bits = if *value == 0.0 { 0 } else { value.to_bits() };
+      uint32_hash_2(hash, (bits >> 32) as u32, bits as u32, HCONST_12);
+    }
+    Term::Pid(pid) => uint32_hash(hash, pid.id(), HCONST_5),
+    Term::Port(port) => uint32_hash(hash, port.id() as u32, HCONST_6),
+    Term::Reference(reference) => {
+      uint32_hash(hash, reference.ids.first().cloned().unwrap_or(0), HCONST_7)
+    }
+    Term::Tuple(Tuple(elements)) => {
+      uint32_hash(hash, elements.len() as u32, HCONST_9);
+      for element in elements.iter() {
+        make_hash2_into(element, hash);
+      }
+    }
+    Term::List(list) => {
+      // Runs of bytes are packed four to a word, like strings.
+      let mut packed = 0u32;
+      let mut packed_len = 0;
+      let mut list = list;
+      loop {
+        for element in list.elements.iter() {
+          match element {
+            Term::Integer(byte @ 0..=255) => {
+              packed = (packed << 8).wrapping_add(*byte as u32);
+              packed_len += 1;
+              if packed_len == 4 {
+                uint32_hash(hash, packed, HCONST_4);
+                packed = 0;
+                packed_len = 0;
+              }
+            }
+            _ => {
+              if packed_len > 0 {
+                uint32_hash(hash, packed, HCONST_4);
+                packed = 0;
+                packed_len = 0;
+              }
+              make_hash2_into(element, hash);
+            }
+          }
+        }
+        match &*list.tail {
+          Term::List(tail) => list = tail,
+          tail => {
+            if packed_len > 0 {
+              uint32_hash(hash, packed, HCONST_4);
+            }
+            make_hash2_into(tail, hash);
+            break;
+          }
+        }
+      }
+    }
+    Term::Binary(Binary(bytes)) => {
+      let initial = HCONST_13.wrapping_add(*hash);
+      *hash = if bytes.is_empty() {
+        initial
+      } else {
+        block_hash(bytes, initial)
+      };
+    }
+    Term::BitBinary(bit_binary) => {
+      // The whole bytes are hashed like a binary, then the bits of the last one.
+      let whole_bytes = &bit_binary.bytes[..bit_binary.bytes.len() - 1];
+      *hash = block_hash(whole_bytes, HCONST_13.wrapping_add(*hash));
+      uint32_hash_2(
+        hash,
+        u32::from(bit_binary.trailing_bits),
+        u32::from(bit_binary.trailing_value()),
+        HCONST_15,
+      );
+    }
+    Term::Export(export) => {
+      uint32_hash_2(hash, export.arity.into(), atom_hash(&export.module), HCONST);
+      uint32_hash(hash, atom_hash(&export.function), HCONST_14);
+    }
+    Term::Fun(fun) => {
+      uint32_hash_2(
+        hash,
+        fun.free_vars.len() as u32,
+        atom_hash(&fun.module),
+        HCONST,
+      );
+      uint32_hash_2(hash, fun.old_index, fun.old_uniq, HCONST);
+      for free_var in fun.free_vars.iter() {
+        make_hash2_into(free_var, hash);
+      }
+    }
+    Term::Map(map) => {
+      // Each pair is hashed on its own and the pairs are combined so that their order does not
+      // matter.
+      uint32_hash(hash, map.len() as u32, HCONST_16);
+      if !map.is_empty() {
+        let pairs = map.iter().fold(0, |pairs, (key, value)| {
+          let mut pair = 0;
+          make_hash2_into(key, &mut pair);
+          make_hash2_into(value, &mut pair);
+          pairs ^ pair
+        });
+        uint32_hash(hash, pairs, HCONST_19);
+      }
+    }
+  }
+}
+
+/// Mixes the bytes of `value`, least significant first.
+fn uint32_hash_step(hash: &mut u32, value: u32, prime: u32) {
+  for shift in &[0, 8, 16, 24] {
+    *hash = hash
+      .wrapping_mul(prime)
+      .wrapping_add((value >> shift) & 0xff);
+  }
+}
+
+fn make_hash(term: &Term, hash: &mut u32) {
+  match canonical(term) {
+    Term::Nil => *hash = hash.wrapping_mul(FUNNY_NUMBER3).wrapping_add(1),
+    Term::Atom(atom) => {
+      *hash = hash
+        .wrapping_mul(FUNNY_NUMBER1)
+        .wrapping_add(atom_hash(atom));
+    }
+    Term::Integer(_) | Term::BigInteger(_) => {
+      // The words of the magnitude are hashed byte by byte, and the last one only on 4 bytes if
+      // its upper half is zero.
+      let (is_negative, words) = integer_words(term);
+      let (last, rest) = words.split_last().unwrap();
+      for word in rest {
+        uint32_hash_step(hash, *word as u32, FUNNY_NUMBER2);
+        uint32_hash_step(hash, (*word >> 32) as u32, FUNNY_NUMBER2);
+      }
+      uint32_hash_step(hash, *last as u32, FUNNY_NUMBER2);
+      if *last >> 32 != 0 {
+        uint32_hash_step(hash, (*last >> 32) as u32, FUNNY_NUMBER2);
+      }
+      *hash = hash.wrapping_mul(if is_negative {
+        FUNNY_NUMBER4
+      } else {
+        FUNNY_NUMBER3
+      });
+    }
+    Term::Float(value) => {
+      let bits = if *value == 0.0 { 0 } else { value.to_bits() };
+      *hash = hash
+        .wrapping_mul(FUNNY_NUMBER6)
+        .wrapping_add((bits >> 32) as u32 ^ bits as u32);
+    }
+    Term::Pid(pid) => {
+      uint32_hash_step(hash, pid.id(), FUNNY_NUMBER5);
+      *hash = hash.wrapping_mul(FUNNY_NUMBER6);
+    }
+    Term::Port(port) => {
+      uint32_hash_step(hash, port.id() as u32, FUNNY_NUMBER9);
+      *hash = hash.wrapping_mul(FUNNY_NUMBER10);
+    }
+    Term::Reference(reference) => {
+      let id = reference.ids.first().cloned().unwrap_or(0);
+      uint32_hash_step(hash, id, FUNNY_NUMBER9);
+      *hash = hash.wrapping_mul(FUNNY_NUMBER10);
+    }
+    Term::Tuple(Tuple(elements)) => {
+      for element in elements.iter() {
+        make_hash(element, hash);
+      }
+      *hash = hash
+        .wrapping_mul(FUNNY_NUMBER9)
+        .wrapping_add(elements.len() as u32);
+    }
+    Term::List(list) => {
+      let mut list = list;
+      loop {
+        for element in list.elements.iter() {
+          match element {
+            Term::Integer(byte @ 0..=255) => {
+              *hash = hash.wrapping_mul(FUNNY_NUMBER2).wrapping_add(*byte as u32);
+            }
+            _ => make_hash(element, hash),
+          }
+        }
+        match &*list.tail {
+          Term::List(tail) => list = tail,
+          tail => {
+            make_hash(tail, hash);
+            *hash = hash.wrapping_mul(FUNNY_NUMBER8);
+            break;
+          }
+        }
+      }
+    }
+    Term::Binary(Binary(bytes)) => {
+      for &byte in bytes.iter() {
+        *hash = hash
+          .wrapping_mul(FUNNY_NUMBER1)
+          .wrapping_add(u32::from(byte));
+      }
+      *hash = hash
+        .wrapping_mul(FUNNY_NUMBER4)
+        .wrapping_add(bytes.len() as u32);
+    }
+    Term::BitBinary(bit_binary) => {
+      let whole_bytes = &bit_binary.bytes[..bit_binary.bytes.len() - 1];
+      for &byte in whole_bytes {
+        *hash = hash
+          .wrapping_mul(FUNNY_NUMBER1)
+          .wrapping_add(u32::from(byte));
+      }
+      *hash = hash
+        .wrapping_mul(FUNNY_NUMBER1)
+        .wrapping_add(u32::from(bit_binary.trailing_value()))
+        .wrapping_mul(FUNNY_NUMBER12)
+        .wrapping_add(u32::from(bit_binary.trailing_bits));
+      *hash = hash
+        .wrapping_mul(FUNNY_NUMBER4)
+        .wrapping_add(whole_bytes.len() as u32);
+    }
+    Term::Export(export) => {
+      *hash = hash
+        .wrapping_mul(FUNNY_NUMBER11)
+        .wrapping_add(export.arity.into())
+        .wrapping_mul(FUNNY_NUMBER1)
+        .wrapping_add(atom_hash(&export.module))
+        .wrapping_mul(FUNNY_NUMBER1)
+        .wrapping_add(atom_hash(&export.function));
+    }
+    Term::Fun(fun) => {
+      *hash = hash
+        .wrapping_mul(FUNNY_NUMBER10)
+        .wrapping_add(fun.free_vars.len() as u32)
+        .wrapping_mul(FUNNY_NUMBER1)
+        .wrapping_add(atom_hash(&fun.module))
+        .wrapping_mul(FUNNY_NUMBER2)
+        .wrapping_add(fun.old_index)
+        .wrapping_mul(FUNNY_NUMBER2)
+        .wrapping_add(fun.old_uniq);
+      for free_var in fun.free_vars.iter() {
+        make_hash(free_var, hash);
+      }
+    }
+    Term::Map(_) => {
+      *hash = hash
+        .wrapping_mul(FUNNY_NUMBER13)
+        .wrapping_add(FUNNY_NUMBER14)
+        .wrapping_add(make_hash2(term));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hashes_fall_in_their_range() {
+    let terms = [
+      "ok",
+      "-1",
+      "1.5",
+      "[a, 1000 | b]",
+      "{ok, \"hi\"}",
+      "#{a => <<1>>}",
+    ];
+    for text in &terms {
+      let term = text.parse::<Term>().unwrap();
+      assert!(phash2_default(&term) < 1 << 27, "{}", text);
+      for &range in &[1, 7, 1000, 1 << 32] {
+        assert!(u64::from(phash2(&term, range).unwrap()) < range, "{}", text);
+        let hash = phash(&term, range).unwrap();
+        assert!(hash >= 1 && hash <= range, "{}", text);
+      }
+    }
+
+    // Terms that are the same in Erlang hash the same.
+    let flat = "[a, b]".parse::<Term>().unwrap();
+    let nested = List::improper(vec![Atom::new("a").unwrap().into()], "[b]".parse().unwrap());
+    assert_eq!(phash2_default(&flat), phash2_default(&nested.into()));
+    let small_big = Term::BigInteger(BigInteger::new(false, vec![5]));
+    assert_eq!(
+      phash2_default(&small_big),
+      phash2_default(&Term::Integer(5))
+    );
+  }
+
+  #[test]
+  fn ranges_are_checked() {
+    assert!(phash2(&Term::Nil, 0).is_err());
+    assert!(phash(&Term::Nil, (1 << 32) + 1).is_err());
+    assert_eq!(phash2(&Term::Nil, 1 << 32).unwrap(), NIL_HASH);
+  }
+
+  fn atom(name: &str) -> Term {
+    Atom::new(name).unwrap().into()
+  }
+
+  fn node() -> Node {
+    Node::new(Atom::new("nonode@nohost").unwrap(), 0)
+  }
+
+  fn bits(bytes: &[u8], trailing_bits: u8) -> Term {
+    BitBinary::new(bytes.to_vec(), trailing_bits)
+      .unwrap()
+      .into()
+  }
+
+  /// Terms with their `erlang:phash2/1`, `erlang:phash2(Term, 1000)` and
+  /// `erlang:phash(Term, 1 bsl 32)`.
+  ///
+  /// The values were computed with a separate transliteration of `erl_term_hashing.c`, not on a
+  /// running node. `scripts/hash_fixtures.escript` prints them for the same terms on a node; its
+  /// output, with the OTP version it prints, replaces these values.
+  fn fixtures() -> Vec<(Term, u32, u32, u64)> {
+    let parse = |text: &str| text.parse::<Term>().unwrap();
+    vec![
+      (atom("ok"), 1883, 883, 1884),
+      (atom("café"), 432_201, 201, 432_202),
+      (atom("Ärger"), 13_340_098, 98, 13_340_099),
+      (parse("0"), 88_723_725, 469, 1),
+      (parse("42"), 30_328_728, 368, 1_169_616_943),
+      (parse("-1"), 44_071_773, 597, 1_680_185_270),
+      (parse("134217727"), 112_602_999, 567, 1_192_343_453),
+      (parse("134217728"), 12_354_923, 755, 2_147_483_673),
+      (parse("-134217729"), 76_739_502, 782, 3_827_674_398),
+      (
+        parse("18446744073709551616"),
+        103_122_609,
+        713,
+        2_788_898_428,
+      ),
+      (
+        parse("-1180591620717411303424"),
+        20_532_417,
+        985,
+        157_674_817,
+      ),
+      (parse("1.5"), 10_380_315, 235, 1_073_217_537),
+      (parse("0.0"), 20_875_736, 920, 1),
+      (parse("[]"), 113_427_502, 702, 2),
+      (parse("[1, 2, 3]"), 25_788_620, 188, 3_336_869_158),
+      (parse("[a, 1000, b]"), 24_244_560, 216, 3_301_683_486),
+      (parse("[1 | 2]"), 86_124_794, 178, 2_402_949_552),
+      (parse("[a | b]"), 74_710_280, 656, 4_159_696_708),
+      (parse("\"hello\""), 81_920_127, 767, 2_340_352_116),
+      (parse("{}"), 87_486_268, 996, 1),
+      (parse("{ok, 1}"), 101_439_905, 377, 2_516_102_183),
+      (parse("{[], []}"), 23_759_702, 342, 4_026_548_527),
+      (parse("#{}"), 39_679_005, 373, 1_113_425_985),
+      (
+        parse("#{a => 1, b => [2]}"),
+        103_078_375,
+        383,
+        1_847_913_995,
+      ),
+      (parse("<<>>"), 13_708_901, 629, 1),
+      (parse("<<\"abc\">>"), 98_228_475, 27, 1_972_007_566),
+      (
+        parse("<<1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13>>"),
+        92_101_820,
+        820,
+        2_025_222_233,
+      ),
+      (bits(&[1 << 5], 3), 73_037_028, 140, 1_345_689_961),
+      (bits(&[b'a', b'b', 5 << 5], 3), 82_827_821, 197, 419_146_526),
+      (
+        Pid::new(node(), 42, 0).into(),
+        100_488_000,
+        840,
+        2_887_412_555,
+      ),
+      (Port::new(node(), 7).into(), 101_549_390, 46, 134_293_116),
+      (
+        Reference {
+          node: node(),
+          ids: vec![1, 2, 3].into_boxed_slice(),
+        }
+        .into(),
+        118_531_908,
+        908,
+        2_473_451_758,
+      ),
+      (
+        Export {
+          module: Atom::new("lists").unwrap(),
+          function: Atom::new("map").unwrap(),
+          arity: 2,
+        }
+        .into(),
+        34_981_515,
+        883,
+        904_024_396,
+      ),
+      (
+        Fun {
+          module: Atom::new("erl_eval").unwrap(),
+          arity: 1,
+          uniq: [0; 16],
+          index: 6,
+          old_index: 6,
+          old_uniq: 80_482_005,
+          pid: Pid::new(node(), 0, 0),
+          free_vars: vec![Term::Integer(1), atom("a")].into_boxed_slice(),
+          encoded: None,
+        }
+        .into(),
+        53_612_290,
+        202,
+        1_242_423_144,
+      ),
+    ]
+  }
+
+  #[test]
+  fn hashes_match_the_runtime() {
+    for (term, phash2_1, phash2_1000, phash_2_32) in fixtures() {
+      assert_eq!(phash2_default(&term), phash2_1, "phash2({})", term);
+      assert_eq!(
+        phash2(&term, 1000).unwrap(),
+        phash2_1000,
+        "phash2({}, 1000)",
+        term
+      );
+      assert_eq!(
+        phash(&term, 1 << 32).unwrap(),
+        phash_2_32,
+        "phash({}, 2^32)",
+        term
+      );
+    }
+  }
+}
@@ -2,25 +2,69 @@
 
 pub use crate::{
   c_node::{CNode, Connection, Listener},
+  convert::{FromTerm, IntoTerm},
   err::{Error, ErrorKind, Result, ResultExt},
+  ext::{
+    binary_to_term, binary_to_term_with_options, term_to_binary, AtomEncoding, CreationFormat,
+    FloatFormat, ReadOptions, WriteOptions,
+  },
+  hash::{phash, phash2, phash2_default},
   name::NodeName,
-  ty::{Atom, ControlMessage, Message, Pid, Reference, Term, TermView, TermViewBuffer, Tuple},
+  parse::{consult, consult_file, consult_with},
+  ty::{
+    Atom, BigInteger, Binary, BitBinary, ControlMessage, ConversionPath, ConversionPathSegment,
+    Export, Fun, List, ListIter, Map, Message, Node, NodeNumbering, NumberedTerm, Pid, Port,
+    Pretty, Reference, Term, TermView, TermViewBuffer, Tuple,
+  },
+};
+
+#[doc(hidden)]
+pub use crate::convert::{read_record, read_record_field, write_record};
+
+#[cfg(feature = "derive")]
+pub use erlang_cnode_derive::{term, FromTerm, IntoTerm};
+
+#[cfg(feature = "serde")]
+pub use crate::{
+  de::{from_bytes, from_bytes_with_options, from_term, from_term_with_options, Deserializer},
+  ser::{
+    to_bytes, to_bytes_with_options, to_term, to_term_with_options, EnumFormat, SerdeOptions,
+    Serializer, StructFormat,
+  },
 };
 
 mod atom;
+mod big_integer;
+mod bit_binary;
 mod c;
 mod c_node;
+mod convert;
+#[cfg(feature = "serde")]
+mod de;
 mod err;
 mod ext;
+mod fragments;
+mod fun;
+mod hash;
+mod list;
+mod map;
 mod message;
 mod name;
 mod node;
+mod order;
+mod parse;
 mod pid;
+mod port;
+mod print;
 mod protocol;
 mod read;
+mod reference;
+#[cfg(feature = "serde")]
+mod ser;
 mod term;
 mod term_view;
 mod ty;
+mod write;
 mod x;
 
 #[macro_export]
@@ -29,3 +73,47 @@ macro_rules! atom {
     $crate::TermView::Atom($p)
   };
 }
+
+#[macro_export]
+macro_rules! int {
+  ($p:pat) => {
+    $crate::TermView::Integer($p)
+  };
+}
+
+#[macro_export]
+macro_rules! binary {
+  ($p:pat) => {
+    $crate::TermView::Binary($p)
+  };
+}
+
+#[macro_export]
+macro_rules! pid {
+  ($p:pat) => {
+    $crate::TermView::Pid($p)
+  };
+}
+
+#[macro_export]
+macro_rules! tuple {
+  ($($p:pat),* $(,)?) => {
+    $crate::TermView::Tuple([$($p),*])
+  };
+}
+
+/// Matches a list: `list![]` is the empty list, `list![a, b]` a proper list of two elements,
+/// `list![a, ..]` a proper list starting with `a` and `list![a, b | tail]` a list of two elements
+/// whose tail matches `tail`.
+#[macro_export]
+macro_rules! list {
+  () => {
+    $crate::TermView::Nil
+  };
+  ($($p:pat),+ | $tail:pat) => {
+    $crate::TermView::List([$($p),+], $tail)
+  };
+  ($($p:pat),+ $(,)?) => {
+    $crate::TermView::List([$($p),+], $crate::TermView::Nil)
+  };
+}
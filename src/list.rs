@@ -0,0 +1,193 @@
+use crate::ty::*;
+
+impl List {
+  /// Creates a proper list, that is a list whose tail is `Term::Nil`.
+  pub fn new<Elements>(elements: Elements) -> Self
+  where
+    Elements: Into<Vec<Term>>,
+  {
+    List::improper(elements, Term::Nil)
+  }
+
+  /// Creates a list with an arbitrary tail, such as `[a | b]`. If the tail is a list, its elements
+  /// are appended, so that `[a | [b]]` is created as `[a, b]`.
+  pub fn improper<Elements>(elements: Elements, mut tail: Term) -> Self
+  where
+    Elements: Into<Vec<Term>>,
+  {
+    let mut elements = elements.into();
+    while let Term::List(list) = tail {
+      elements.extend(Vec::from(list.elements));
+      tail = *list.tail;
+    }
+    List {
+      elements: elements.into_boxed_slice(),
+      tail: Box::new(tail),
+    }
+  }
+
+  pub fn is_proper(&self) -> bool {
+    match self.tail() {
+      Term::Nil => true,
+      _ => false,
+    }
+  }
+
+  /// Returns the number of elements, including those of the lists in the tail but not the tail.
+  pub fn len(&self) -> usize {
+    self.iter().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Iterates over the elements, then over those of the lists in the tail, not including the final
+  /// tail.
+  pub fn iter(&self) -> ListIter {
+    ListIter {
+      elements: self.elements.iter(),
+      tail: &self.tail,
+    }
+  }
+
+  /// Returns the tail after the lists in the tail, which is never a list.
+  pub fn tail(&self) -> &Term {
+    let mut tail = &*self.tail;
+    while let Term::List(list) = tail {
+      tail = &list.tail;
+    }
+    tail
+  }
+}
+
+impl<'list> Iterator for ListIter<'list> {
+  type Item = &'list Term;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      if let Some(element) = self.elements.next() {
+        return Some(element);
+      }
+      match self.tail {
+        Term::List(list) => {
+          self.elements = list.elements.iter();
+          self.tail = &list.tail;
+        }
+        _ => return None,
+      }
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let mut len = self.elements.len();
+    let mut tail = self.tail;
+    while let Term::List(list) = tail {
+      len += list.elements.len();
+      tail = &list.tail;
+    }
+    (len, Some(len))
+  }
+}
+
+impl<'list> ExactSizeIterator for ListIter<'list> {}
+
+impl<'list> IntoIterator for &'list List {
+  type Item = &'list Term;
+  type IntoIter = ListIter<'list>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{phash, phash2_default, ty::*, FromTerm};
+
+  fn empty_list() -> Term {
+    Term::List(List::new(vec![]))
+  }
+
+  /// Builds `[head | tail]` without moving the elements of `tail` into the list.
+  fn cons(head: Term, tail: Term) -> Term {
+    Term::List(List {
+      elements: vec![head].into_boxed_slice(),
+      tail: Box::new(tail),
+    })
+  }
+
+  fn string(text: &str) -> Term {
+    List::new(
+      text
+        .bytes()
+        .map(|byte| Term::Integer(byte.into()))
+        .collect::<Vec<_>>(),
+    )
+    .into()
+  }
+
+  #[test]
+  fn empty_list_converts_to_nil() {
+    assert!(matches!(Term::from(List::new(vec![])), Term::Nil));
+    let tail = Term::Atom(Atom::new("a").unwrap());
+    assert_eq!(Term::from(List::improper(vec![], tail.clone())), tail);
+  }
+
+  #[test]
+  fn empty_list_behaves_like_nil() {
+    assert_eq!(empty_list().to_string(), "[]");
+    assert_eq!(format!("{:#}", empty_list()), "[]");
+    assert_eq!(empty_list(), Term::Nil);
+    assert_eq!(phash2_default(&empty_list()), phash2_default(&Term::Nil));
+    assert_eq!(
+      phash(&empty_list(), 1 << 32).unwrap(),
+      phash(&Term::Nil, 1 << 32).unwrap()
+    );
+    assert_eq!(
+      crate::term_to_binary(&empty_list(), Default::default()).unwrap(),
+      crate::term_to_binary(&Term::Nil, Default::default()).unwrap()
+    );
+  }
+
+  #[test]
+  fn tail_lists_are_moved_into_the_elements() {
+    let list = List::improper(vec![Term::Integer(1)], string("b"));
+    assert_eq!(list.elements[..], [Term::Integer(1), Term::Integer(98)]);
+    assert_eq!(list.tail, Box::new(Term::Nil));
+  }
+
+  #[test]
+  fn tail_lists_are_part_of_the_list() {
+    let chain = cons(Term::Integer(1), cons(Term::Integer(2), Term::Nil));
+    let list = match &chain {
+      Term::List(list) => list,
+      _ => unreachable!(),
+    };
+    assert!(list.is_proper());
+    assert_eq!(list.len(), 2);
+    assert_eq!(
+      list.iter().collect::<Vec<_>>(),
+      [&Term::Integer(1), &Term::Integer(2)]
+    );
+    assert_eq!(list.tail(), &Term::Nil);
+
+    let flat = List::new(vec![Term::Integer(1), Term::Integer(2)]).into();
+    assert_eq!(chain, flat);
+    assert_eq!(Vec::<i32>::from_term(&chain).unwrap(), [1, 2]);
+    assert_eq!(chain.to_string(), "[1,2]");
+    assert_eq!(
+      crate::term_to_binary(&chain, Default::default()).unwrap(),
+      crate::term_to_binary(&flat, Default::default()).unwrap()
+    );
+
+    let chain = cons(Term::Integer(97), string("bc"));
+    assert_eq!(chain.to_string(), "\"abc\"");
+    assert_eq!(String::from_term(&chain).unwrap(), "abc");
+    let chain = cons(
+      Term::Integer(1),
+      cons(Term::Integer(2), Term::Atom(Atom::new("a").unwrap())),
+    );
+    assert_eq!(chain.to_string(), "[1,2|a]");
+  }
+}
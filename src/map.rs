@@ -0,0 +1,36 @@
+use crate::ty::*;
+use std::slice;
+
+impl Map {
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  /// Returns the value associated with `key`, if any.
+  ///
+  /// Maps decoded from the external term format are not indexed, so this is a linear search.
+  pub fn get(&self, key: &Term) -> Option<&Term> {
+    self
+      .0
+      .iter()
+      .find(|(entry_key, _)| entry_key == key)
+      .map(|(_, value)| value)
+  }
+
+  pub fn iter(&self) -> slice::Iter<(Term, Term)> {
+    self.0.iter()
+  }
+}
+
+impl<'map> IntoIterator for &'map Map {
+  type Item = &'map (Term, Term);
+  type IntoIter = slice::Iter<'map, (Term, Term)>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
@@ -1,17 +1,77 @@
-use crate::{ext, read, ty::*};
+use crate::{convert::FromTerm, err::*, ext, read, ty::*};
+use std::os;
+
+const SEND: i64 = 2;
+const REG_SEND: i64 = 6;
+const SEND_TT: i64 = 12;
+const REG_SEND_TT: i64 = 16;
+const SEND_SENDER: i64 = 22;
+const SEND_SENDER_TT: i64 = 23;
 
 impl ControlMessage {
+  /// Reads the control message that starts a message, for messages that the C library did not
+  /// read, such as the ones that were reassembled from fragments. Only the messages that carry a
+  /// term are supported.
+  pub fn read<'input>(
+    input: &'input [u8],
+    atom_references: &AtomCacheReferences,
+  ) -> read::IResult<'input, Self> {
+    let (input, control_message) = ext::read_term(input, atom_references)?;
+    let Tuple(elements) = Tuple::from_term(&control_message)?;
+    let message_type = match elements.first() {
+      Some(message_type) => i64::from_term(message_type)?,
+      None => return Err(ErrorKind::UnknownMessageType(-1).into()),
+    };
+    let control_message = match (message_type, &elements[..]) {
+      (SEND, [_, _, to]) => ControlMessage::Send {
+        from: None,
+        to: Pid::from_term(to)?,
+        trace_token: None,
+      },
+      (SEND_TT, [_, _, to, trace_token]) => ControlMessage::Send {
+        from: None,
+        to: Pid::from_term(to)?,
+        trace_token: Some(read_trace_token(trace_token)?),
+      },
+      (SEND_SENDER, [_, from, to]) => ControlMessage::Send {
+        from: Some(Pid::from_term(from)?),
+        to: Pid::from_term(to)?,
+        trace_token: None,
+      },
+      (SEND_SENDER_TT, [_, from, to, trace_token]) => ControlMessage::Send {
+        from: Some(Pid::from_term(from)?),
+        to: Pid::from_term(to)?,
+        trace_token: Some(read_trace_token(trace_token)?),
+      },
+      (REG_SEND, [_, from, _, to]) => ControlMessage::RegisteredSend {
+        from: Pid::from_term(from)?,
+        to: Atom::from_term(to)?,
+        trace_token: None,
+      },
+      (REG_SEND_TT, [_, from, _, to, trace_token]) => ControlMessage::RegisteredSend {
+        from: Pid::from_term(from)?,
+        to: Atom::from_term(to)?,
+        trace_token: Some(read_trace_token(trace_token)?),
+      },
+      _ => {
+        return Err(ErrorKind::UnknownMessageType(message_type as os::raw::c_long).into());
+      }
+    };
+    Ok((input, control_message))
+  }
+
   pub fn read_message<'input>(
     self,
     input: &'input [u8],
-    atom_cache: &AtomCache,
+    atom_references: &AtomCacheReferences,
+    options: ext::ReadOptions,
   ) -> read::IResult<'input, Message> {
     match self {
       ControlMessage::Send {
         from,
         to,
         trace_token,
-      } => ext::read_term(input, atom_cache).map(|(input, term)| {
+      } => ext::read_outer_term(input, atom_references, options).map(|(input, term)| {
         (
           input,
           Message::Send {
@@ -26,7 +86,7 @@ impl ControlMessage {
         from,
         to,
         trace_token,
-      } => ext::read_term(input, atom_cache).map(|(input, term)| {
+      } => ext::read_outer_term(input, atom_references, options).map(|(input, term)| {
         (
           input,
           Message::RegisteredSend {
@@ -43,3 +103,70 @@ impl ControlMessage {
     }
   }
 }
+
+/// Reads a trace token, which is a tuple of its flags, label, serial, sender and previous serial.
+fn read_trace_token(term: &Term) -> Result<TraceToken> {
+  let (flags, label, serial, from, previous) = FromTerm::from_term(term)?;
+  Ok(TraceToken {
+    serial,
+    previous,
+    from,
+    label,
+    flags,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn read(control_message: &str) -> Message {
+    let mut bytes = Vec::new();
+    ext::write_term(
+      &mut bytes,
+      &control_message.parse().unwrap(),
+      Default::default(),
+    )
+    .unwrap();
+    ext::write_term(&mut bytes, &Term::Integer(1), Default::default()).unwrap();
+    let references = AtomCacheReferences::new();
+    let (input, control_message) = ControlMessage::read(&bytes, &references).unwrap();
+    let (input, message) = control_message
+      .read_message(input, &references, Default::default())
+      .unwrap();
+    assert!(input.is_empty());
+    message
+  }
+
+  #[test]
+  fn sends_are_read_with_or_without_sender() {
+    let to = "<0.1.0>".parse::<Term>().unwrap();
+    let from = "<0.2.0>".parse::<Term>().unwrap();
+    let trace_token = "{0, 1, 2, <0.2.0>, 3}";
+    for (control_message, expected_from, has_trace_token) in &[
+      ("{2, '', <0.1.0>}".to_string(), None, false),
+      (format!("{{12, '', <0.1.0>, {}}}", trace_token), None, true),
+      ("{22, <0.2.0>, <0.1.0>}".to_string(), Some(&from), false),
+      (
+        format!("{{23, <0.2.0>, <0.1.0>, {}}}", trace_token),
+        Some(&from),
+        true,
+      ),
+    ] {
+      match read(control_message) {
+        Message::Send {
+          from,
+          to: actual_to,
+          trace_token,
+          term,
+        } => {
+          assert_eq!(from.map(Term::from).as_ref(), *expected_from);
+          assert_eq!(Term::from(actual_to), to);
+          assert_eq!(trace_token.is_some(), *has_trace_token);
+          assert_eq!(term, Term::Integer(1));
+        }
+        message => panic!("unexpected message {:?}", message),
+      }
+    }
+  }
+}
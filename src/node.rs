@@ -1,16 +1,66 @@
-use crate::{err::*, ty::*};
+use crate::ty::*;
+use std::cell;
+
+/// The name of the local node of a runtime that is not distributed.
+pub(crate) const NOT_DISTRIBUTED_NODE: &str = "nonode@nohost";
 
 impl Node {
-  pub fn new(name: Atom, serial_number: u32) -> Result<Self> {
-    const SERIAL_NUMBER_MAX: u32 = (1 << 2) - 1;
+  /// Creates a new `Node`.
+  ///
+  /// The serial number, also called the creation, is stored in full. Peers that only support the
+  /// legacy 2-bit creations will reject nodes whose serial number is out of that range when the
+  /// node is encoded.
+  pub fn new(name: Atom, serial_number: u32) -> Self {
+    Node {
+      name,
+      serial_number,
+    }
+  }
+
+  pub fn serial_number(&self) -> u32 {
+    self.serial_number
+  }
+}
 
-    if serial_number <= SERIAL_NUMBER_MAX {
-      Ok(Node {
-        name,
-        serial_number: serial_number as u8,
-      })
-    } else {
-      Err(ErrorKind::NodeSerialNumberOutOfRange(name, serial_number).into())
+impl NodeNumbering {
+  /// Creates a numbering in which `local` is 0 and no other node is numbered yet.
+  pub fn new(local: Node) -> Self {
+    NodeNumbering {
+      local,
+      others: cell::RefCell::new(Vec::new()),
     }
   }
+
+  /// Returns the number of `node`, numbering it after the nodes that were numbered before if it is
+  /// new. Like in the runtime, the same name with another serial number is another node.
+  pub fn number(&self, node: &Node) -> usize {
+    if *node == self.local {
+      return 0;
+    }
+    let mut others = self.others.borrow_mut();
+    let index = match others.iter().position(|other| other == node) {
+      Some(index) => index,
+      None => {
+        others.push(node.clone());
+        others.len() - 1
+      }
+    };
+    index + 1
+  }
+
+  /// Returns the node that is numbered `number`, if any.
+  pub fn node(&self, number: usize) -> Option<Node> {
+    match number {
+      0 => Some(self.local.clone()),
+      _ => self.others.borrow().get(number - 1).cloned(),
+    }
+  }
+}
+
+/// Numbers `nonode@nohost` 0, like a runtime that is not distributed.
+impl Default for NodeNumbering {
+  fn default() -> Self {
+    let local = Atom::new(NOT_DISTRIBUTED_NODE).expect("the name of the local node is an atom");
+    NodeNumbering::new(Node::new(local, 0))
+  }
 }
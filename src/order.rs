@@ -0,0 +1,540 @@
+use crate::ty::*;
+use num_traits::ToPrimitive;
+use std::{
+  cmp,
+  collections::hash_map::DefaultHasher,
+  hash::{Hash, Hasher},
+};
+
+impl Term {
+  /// Compares terms like Erlang's comparison operators, such as `<` and `==`, which coerce
+  /// integers and floats so that `1 == 1.0`.
+  ///
+  /// Unlike `Ord`, this is not consistent with `Eq`: use it to reproduce what Erlang code would
+  /// decide, and `Ord` to sort or index terms.
+  pub fn compare(&self, other: &Term) -> cmp::Ordering {
+    compare(self, other, Mode::Coerce)
+  }
+
+  /// Returns whether the terms are equal like with Erlang's `==` operator, so that `1` equals `1.0`.
+  pub fn equals(&self, other: &Term) -> bool {
+    self.compare(other) == cmp::Ordering::Equal
+  }
+}
+
+/// How numbers of different types are compared.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Mode {
+  /// Integers come before floats of the same value, like map keys.
+  Exact,
+  /// Integers equal floats of the same value.
+  Coerce,
+}
+
+/// The rank of lists that have elements, see `rank`.
+const LIST_RANK: u8 = 9;
+
+/// The position of each type in the standard order of terms: number < atom < reference < fun <
+/// port < pid < tuple < map < nil < list < bitstring.
+fn rank(term: &Term) -> u8 {
+  match term {
+    Term::Integer(_) | Term::BigInteger(_) | Term::Float(_) => 0,
+    Term::Atom(_) => 1,
+    Term::Reference(_) => 2,
+    Term::Fun(_) | Term::Export(_) => 3,
+    Term::Port(_) => 4,
+    Term::Pid(_) => 5,
+    Term::Tuple(_) => 6,
+    Term::Map(_) => 7,
+    Term::Nil => 8,
+    Term::List(_) => LIST_RANK,
+    Term::Binary(_) | Term::BitBinary(_) => 10,
+  }
+}
+
+/// Skips the lists that have no elements, which only stand for their tail.
+pub(crate) fn canonical(mut term: &Term) -> &Term {
+  while let Term::List(list) = term {
+    if !list.elements.is_empty() {
+      break;
+    }
+    term = &list.tail;
+  }
+  term
+}
+
+fn compare(left: &Term, right: &Term, mode: Mode) -> cmp::Ordering {
+  let left = canonical(left);
+  let right = canonical(right);
+  match (left, right) {
+    (Term::Integer(left), Term::Integer(right)) => left.cmp(right),
+    (Term::Float(left), Term::Float(right)) => compare_floats(*left, *right, mode),
+    (Term::Float(left), right) if rank(right) == 0 => {
+      compare_integer_float(&to_big_integer(right), *left, mode).reverse()
+    }
+    (left, Term::Float(right)) if rank(left) == 0 => {
+      compare_integer_float(&to_big_integer(left), *right, mode)
+    }
+    (left, right) if rank(left) == 0 && rank(right) == 0 => {
+      to_big_integer(left).cmp(&to_big_integer(right))
+    }
+    (Term::Atom(left), Term::Atom(right)) => left.cmp(right),
+    (Term::Reference(left), Term::Reference(right)) => left.cmp(right),
+    (Term::Fun(left), Term::Fun(right)) => compare_funs(left, right, mode),
+    (Term::Export(left), Term::Export(right)) => left.cmp(right),
+    // Closures come before external functions.
+    (Term::Fun(_), Term::Export(_)) => cmp::Ordering::Less,
+    (Term::Export(_), Term::Fun(_)) => cmp::Ordering::Greater,
+    (Term::Port(left), Term::Port(right)) => left.cmp(right),
+    (Term::Pid(left), Term::Pid(right)) => left.cmp(right),
+    (Term::Tuple(left), Term::Tuple(right)) => compare_tuples(left, right, mode),
+    (Term::Map(left), Term::Map(right)) => compare_maps(left, right, mode),
+    (Term::List(left), Term::List(right)) => compare_lists(left, right, mode),
+    (left, right) if rank(left) == 10 && rank(right) == 10 => bits(left).cmp(&bits(right)),
+    (Term::Nil, Term::Nil) => cmp::Ordering::Equal,
+    (left, right) => rank(left).cmp(&rank(right)),
+  }
+}
+
+/// Returns the bytes and the size in bits of a bitstring. Comparing them compares the bitstrings
+/// bit by bit since the unused bits of the last byte are zero.
+fn bits(term: &Term) -> (&[u8], usize) {
+  match term {
+    Term::Binary(Binary(bytes)) => (bytes, bytes.len() * 8),
+    Term::BitBinary(bit_binary) => (bit_binary.bytes(), bit_binary.bit_len()),
+    _ => unreachable!(),
+  }
+}
+
+fn compare_tuples(Tuple(left): &Tuple, Tuple(right): &Tuple, mode: Mode) -> cmp::Ordering {
+  left
+    .len()
+    .cmp(&right.len())
+    .then_with(|| compare_all(left, right, mode))
+}
+
+/// Compares closures like the runtime, by module, by their index and hash in the module, then by
+/// their free variables.
+fn compare_funs(left: &Fun, right: &Fun, mode: Mode) -> cmp::Ordering {
+  left
+    .module
+    .cmp(&right.module)
+    .then_with(|| left.old_index.cmp(&right.old_index))
+    .then_with(|| left.old_uniq.cmp(&right.old_uniq))
+    .then_with(|| left.free_vars.len().cmp(&right.free_vars.len()))
+    .then_with(|| compare_all(&left.free_vars, &right.free_vars, mode))
+}
+
+fn compare_all(left: &[Term], right: &[Term], mode: Mode) -> cmp::Ordering {
+  left
+    .iter()
+    .zip(right)
+    .map(|(left, right)| compare(left, right, mode))
+    .find(|&ordering| ordering != cmp::Ordering::Equal)
+    .unwrap_or_else(|| left.len().cmp(&right.len()))
+}
+
+fn to_big_integer(term: &Term) -> BigInteger {
+  match term {
+    Term::Integer(value) => BigInteger::from(i64::from(*value)),
+    Term::BigInteger(value) => value.clone(),
+    _ => unreachable!(),
+  }
+}
+
+fn compare_floats(left: f64, right: f64, mode: Mode) -> cmp::Ordering {
+  match left.partial_cmp(&right) {
+    Some(cmp::Ordering::Equal) if mode == Mode::Exact => {
+      // Only 0.0 and -0.0 compare equal with different bits.
+      right.is_sign_negative().cmp(&left.is_sign_negative())
+    }
+    Some(ordering) => ordering,
+    None => left.to_bits().cmp(&right.to_bits()),
+  }
+}
+
+fn compare_integer_float(integer: &BigInteger, float: f64, mode: Mode) -> cmp::Ordering {
+  if !float.is_finite() {
+    return integer
+      .to_f64()
+      .unwrap()
+      .partial_cmp(&float)
+      .unwrap_or(cmp::Ordering::Less);
+  }
+  let integral = float.trunc();
+  integer
+    .cmp(&BigInteger::from_integral_f64(integral))
+    .then_with(|| 0.0.partial_cmp(&(float - integral)).unwrap())
+    .then_with(|| match mode {
+      Mode::Exact => cmp::Ordering::Less,
+      Mode::Coerce => cmp::Ordering::Equal,
+    })
+}
+
+/// Compares maps by size, then by their keys in order, then by their values in the order of the
+/// keys. Keys are always compared exactly.
+fn compare_maps(left: &Map, right: &Map, mode: Mode) -> cmp::Ordering {
+  let left = sorted_entries(left);
+  let right = sorted_entries(right);
+  left
+    .len()
+    .cmp(&right.len())
+    .then_with(|| {
+      left
+        .iter()
+        .zip(&right)
+        .map(|((left, _), (right, _))| compare(left, right, Mode::Exact))
+        .find(|&ordering| ordering != cmp::Ordering::Equal)
+        .unwrap_or(cmp::Ordering::Equal)
+    })
+    .then_with(|| {
+      left
+        .iter()
+        .zip(&right)
+        .map(|((_, left), (_, right))| compare(left, right, mode))
+        .find(|&ordering| ordering != cmp::Ordering::Equal)
+        .unwrap_or(cmp::Ordering::Equal)
+    })
+}
+
+fn sorted_entries(map: &Map) -> Vec<&(Term, Term)> {
+  let mut entries = map.iter().collect::<Vec<_>>();
+  entries.sort_by(|(left, _), (right, _)| compare(left, right, Mode::Exact));
+  entries
+}
+
+/// Compares lists element by element, then by their tails, like chains of cons cells. Since a tail
+/// is never a list, the tail of the list that runs out of elements first is only compared by type
+/// with the rest of the other list.
+fn compare_lists(left: &List, right: &List, mode: Mode) -> cmp::Ordering {
+  let mut left_elements = left.iter();
+  let mut right_elements = right.iter();
+  loop {
+    match (left_elements.next(), right_elements.next()) {
+      (Some(left), Some(right)) => match compare(left, right, mode) {
+        cmp::Ordering::Equal => (),
+        ordering => return ordering,
+      },
+      (None, None) => return compare(left.tail(), right.tail(), mode),
+      (None, Some(_)) => return rank(left.tail()).cmp(&LIST_RANK),
+      (Some(_), None) => return LIST_RANK.cmp(&rank(right.tail())),
+    }
+  }
+}
+
+impl Hash for Term {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    let term = canonical(self);
+    rank(term).hash(state);
+    match term {
+      Term::Nil => {}
+      Term::Integer(value) => i64::from(*value).hash(state),
+      Term::BigInteger(value) => match value.to_i64() {
+        Some(value) => value.hash(state),
+        None => value.hash(state),
+      },
+      Term::Float(value) => {
+        // Floats never equal integers exactly, so they can hash apart from them.
+        true.hash(state);
+        value.to_bits().hash(state);
+      }
+      Term::Atom(atom) => atom.hash(state),
+      Term::Pid(pid) => pid.hash(state),
+      Term::Port(port) => port.hash(state),
+      Term::Reference(reference) => reference.hash(state),
+      Term::Tuple(tuple) => tuple.hash(state),
+      Term::List(list) => list.hash(state),
+      Term::Binary(binary) => binary.hash(state),
+      Term::BitBinary(bit_binary) => bit_binary.hash(state),
+      Term::Map(map) => map.hash(state),
+      Term::Export(export) => export.hash(state),
+      Term::Fun(fun) => fun.hash(state),
+    }
+  }
+}
+
+macro_rules! impl_exact_order {
+  ($($ty:ident => $compare:ident),*) => {
+    $(
+      impl Ord for $ty {
+        fn cmp(&self, other: &Self) -> cmp::Ordering {
+          $compare(self, other, Mode::Exact)
+        }
+      }
+
+      impl PartialOrd for $ty {
+        fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+          Some(self.cmp(other))
+        }
+      }
+
+      impl PartialEq for $ty {
+        fn eq(&self, other: &Self) -> bool {
+          self.cmp(other) == cmp::Ordering::Equal
+        }
+      }
+
+      impl Eq for $ty {}
+    )*
+  };
+}
+
+impl_exact_order! {
+  Term => compare,
+  Tuple => compare_tuples,
+  List => compare_lists,
+  Map => compare_maps,
+  Fun => compare_funs
+}
+
+impl Hash for Tuple {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.0.hash(state)
+  }
+}
+
+impl Hash for Fun {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.module.hash(state);
+    self.old_index.hash(state);
+    self.old_uniq.hash(state);
+    self.free_vars.hash(state);
+  }
+}
+
+/// Hashes the elements of the list and of the lists in its tail, then the final tail, so that
+/// `[a | [b]]` hashes like `[a, b]`.
+impl Hash for List {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    for element in self {
+      element.hash(state);
+    }
+    self.tail().hash(state);
+  }
+}
+
+/// Hashes the entries regardless of their order, like they are compared.
+impl Hash for Map {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    let combined = self.iter().fold(0u64, |combined, entry| {
+      let mut hasher = DefaultHasher::new();
+      entry.hash(&mut hasher);
+      combined.wrapping_add(hasher.finish())
+    });
+    self.len().hash(state);
+    combined.hash(state);
+  }
+}
+
+/// Orders pids by node, then like the runtime packs their serial above their id.
+impl Ord for Pid {
+  fn cmp(&self, other: &Self) -> cmp::Ordering {
+    self
+      .node
+      .cmp(&other.node)
+      .then_with(|| self.serial.cmp(&other.serial))
+      .then_with(|| self.id.cmp(&other.id))
+  }
+}
+
+impl PartialOrd for Pid {
+  fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+/// Orders references by node, then by their number of words, then by their most significant words.
+impl Ord for Reference {
+  fn cmp(&self, other: &Self) -> cmp::Ordering {
+    self
+      .node
+      .cmp(&other.node)
+      .then_with(|| self.ids.len().cmp(&other.ids.len()))
+      .then_with(|| self.ids.iter().rev().cmp(other.ids.iter().rev()))
+  }
+}
+
+impl PartialOrd for Reference {
+  fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::{BTreeMap, HashMap};
+
+  fn atom(name: &str) -> Term {
+    Term::Atom(Atom::new(name).unwrap())
+  }
+
+  fn node() -> Node {
+    Node::new(Atom::new("a@b").unwrap(), 1)
+  }
+
+  fn hash(term: &Term) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    term.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  #[test]
+  fn types_are_ranked() {
+    let ranked = vec![
+      Term::Integer(-1),
+      Term::Float(0.5),
+      Term::from(1i64 << 40),
+      atom("a"),
+      Term::Reference(Reference {
+        node: node(),
+        ids: vec![1].into_boxed_slice(),
+      }),
+      Port::new(node(), 1).into(),
+      Pid::new(node(), 1, 0).into(),
+      Tuple(vec![].into_boxed_slice()).into(),
+      Map(vec![].into_boxed_slice()).into(),
+      Term::Nil,
+      List::new(vec![Term::Integer(1)]).into(),
+      Binary(vec![].into_boxed_slice()).into(),
+    ];
+    for (index, left) in ranked.iter().enumerate() {
+      for (other_index, right) in ranked.iter().enumerate() {
+        assert_eq!(
+          left.cmp(right),
+          index.cmp(&other_index),
+          "{:?} {:?}",
+          left,
+          right
+        );
+        assert_eq!(
+          left.compare(right),
+          index.cmp(&other_index),
+          "{:?} {:?}",
+          left,
+          right
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn numbers_are_coerced_only_by_compare() {
+    let one = Term::Integer(1);
+    let one_float = Term::Float(1.0);
+    assert!(one < one_float);
+    assert_ne!(one, one_float);
+    assert_eq!(one.compare(&one_float), cmp::Ordering::Equal);
+    assert!(one.equals(&one_float));
+    assert!(Term::Float(0.5) < one);
+    assert!(one < Term::Float(1.5));
+    assert!(Term::from(1i64 << 40) > Term::Float(1e12));
+    assert!(Term::from(1i64 << 40).equals(&Term::Float(1_099_511_627_776.0)));
+
+    // Big integers that fit in an integer are the same term as the integer.
+    let small_big = Term::BigInteger(BigInteger::new(false, vec![5]));
+    assert_eq!(small_big, Term::Integer(5));
+    assert_eq!(hash(&small_big), hash(&Term::Integer(5)));
+  }
+
+  #[test]
+  fn zeros_are_distinct_and_hashed_apart() {
+    let zero = Term::Float(0.0);
+    let negative_zero = Term::Float(-0.0);
+    assert_ne!(zero, negative_zero);
+    assert!(negative_zero < zero);
+    assert_ne!(hash(&zero), hash(&negative_zero));
+    assert_eq!(hash(&zero), hash(&Term::Float(0.0)));
+    assert!(zero.equals(&negative_zero));
+    assert!(zero.equals(&Term::Integer(0)));
+  }
+
+  #[test]
+  fn maps_are_ordered_by_sorted_keys() {
+    let map = |entries: Vec<(Term, Term)>| Term::from(Map(entries.into_boxed_slice()));
+    let ab = map(vec![
+      (atom("a"), Term::Integer(1)),
+      (atom("b"), Term::Integer(2)),
+    ]);
+    let ba = map(vec![
+      (atom("b"), Term::Integer(2)),
+      (atom("a"), Term::Integer(1)),
+    ]);
+    assert_eq!(ab, ba);
+    assert_eq!(hash(&ab), hash(&ba));
+
+    // Smaller maps come first, then maps with smaller keys, then with smaller values.
+    let c = map(vec![(atom("c"), Term::Integer(0))]);
+    assert!(c < ab);
+    let ac = map(vec![
+      (atom("a"), Term::Integer(1)),
+      (atom("c"), Term::Integer(0)),
+    ]);
+    assert!(ab < ac);
+    let ab3 = map(vec![
+      (atom("b"), Term::Integer(3)),
+      (atom("a"), Term::Integer(1)),
+    ]);
+    assert!(ab < ab3);
+
+    // Keys are compared exactly, values are coerced by `compare`.
+    let integer_key = map(vec![(Term::Integer(1), atom("x"))]);
+    let float_key = map(vec![(Term::Float(1.0), atom("x"))]);
+    assert_eq!(integer_key.compare(&float_key), cmp::Ordering::Less);
+    let integer_value = map(vec![(atom("x"), Term::Integer(1))]);
+    let float_value = map(vec![(atom("x"), Term::Float(1.0))]);
+    assert!(integer_value.equals(&float_value));
+    assert!(integer_value < float_value);
+  }
+
+  #[test]
+  fn lists_compare_like_cons_cells() {
+    let flat = Term::from(List::new(vec![atom("a"), atom("b")]));
+    let nested = Term::from(List::improper(
+      vec![atom("a")],
+      List::new(vec![atom("b")]).into(),
+    ));
+    assert_eq!(flat, nested);
+    assert_eq!(hash(&flat), hash(&nested));
+    assert_eq!(Term::from(List::new(vec![])), Term::Nil);
+
+    assert!(Term::from(List::new(vec![atom("a")])) < flat);
+    let improper = Term::from(List::improper(vec![atom("a")], atom("b")));
+    assert!(improper < flat);
+  }
+
+  #[test]
+  fn terms_index_collections() {
+    let keys = [
+      Term::Float(1.0),
+      atom("b"),
+      Term::Integer(1),
+      List::new(vec![Term::Integer(1)]).into(),
+      atom("a"),
+    ];
+
+    let mut sorted = BTreeMap::new();
+    let mut hashed = HashMap::new();
+    for (index, key) in keys.iter().enumerate() {
+      sorted.insert(key.clone(), index);
+      hashed.insert(key.clone(), index);
+    }
+    assert_eq!(
+      sorted.keys().cloned().collect::<Vec<_>>(),
+      [
+        Term::Integer(1),
+        Term::Float(1.0),
+        atom("a"),
+        atom("b"),
+        List::new(vec![Term::Integer(1)]).into(),
+      ]
+    );
+    assert_eq!(hashed.len(), keys.len());
+    for (index, key) in keys.iter().enumerate() {
+      assert_eq!(sorted[key], index);
+      assert_eq!(hashed[key], index);
+    }
+    let nested = List::improper(vec![], List::new(vec![Term::Integer(1)]).into()).into();
+    assert_eq!(hashed.get(&nested), Some(&3));
+  }
+}
@@ -0,0 +1,849 @@
+use crate::{
+  atom::{is_lowercase_start, is_name_char, RESERVED_WORDS},
+  err::*,
+  ty::*,
+};
+use std::{fs, path, str};
+
+/// Parses a single term, such as `{ok, [1, 2, 3]}`, optionally terminated by a dot. Pids are read
+/// with the default `NodeNumbering`, see `Term::parse_with`.
+impl str::FromStr for Term {
+  type Err = Error;
+
+  fn from_str(text: &str) -> Result<Self> {
+    Term::parse_with(text, &NodeNumbering::default())
+  }
+}
+
+impl Term {
+  /// Parses a single term like `FromStr`, reading the first number of a pid such as `<5.42.0>` as
+  /// the number of its node in `numbering`. Like `list_to_pid/1`, a number that `numbering` has not
+  /// given to any node is an error.
+  pub fn parse_with(text: &str, numbering: &NodeNumbering) -> Result<Term> {
+    let mut parser = Parser::new(text, numbering);
+    let term = parser.term()?;
+    parser.skip_layout();
+    if parser.peek() == Some('.') {
+      parser.dot()?;
+    }
+    parser.skip_layout();
+    if parser.peek().is_some() {
+      return Err(parser.error("expected the end of the input"));
+    }
+    Ok(term)
+  }
+}
+
+/// Parses a sequence of terms, each terminated by a dot, like `file:consult/1`. Pids are read with
+/// the default `NodeNumbering`.
+pub fn consult(text: &str) -> Result<Vec<Term>> {
+  consult_with(text, &NodeNumbering::default())
+}
+
+/// Parses a sequence of terms like `consult`, reading the nodes of pids from `numbering` like
+/// `Term::parse_with`.
+pub fn consult_with(text: &str, numbering: &NodeNumbering) -> Result<Vec<Term>> {
+  let mut parser = Parser::new(text, numbering);
+  let mut terms = Vec::new();
+  loop {
+    parser.skip_layout();
+    if parser.peek().is_none() {
+      return Ok(terms);
+    }
+    terms.push(parser.term()?);
+    parser.skip_layout();
+    parser.dot()?;
+  }
+}
+
+/// Reads and parses a file of terms, each terminated by a dot, like `file:consult/1`.
+pub fn consult_file<P>(path: P) -> Result<Vec<Term>>
+where
+  P: AsRef<path::Path>,
+{
+  consult(&fs::read_to_string(path)?)
+}
+
+struct Parser<'numbering> {
+  numbering: &'numbering NodeNumbering,
+  chars: Vec<char>,
+  position: usize,
+  line: usize,
+  column: usize,
+}
+
+impl<'numbering> Parser<'numbering> {
+  fn new(text: &str, numbering: &'numbering NodeNumbering) -> Self {
+    Parser {
+      numbering,
+      chars: text.chars().collect(),
+      position: 0,
+      line: 1,
+      column: 1,
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.peek_at(0)
+  }
+
+  fn peek_at(&self, offset: usize) -> Option<char> {
+    self.chars.get(self.position + offset).cloned()
+  }
+
+  fn next(&mut self) -> Option<char> {
+    let c = self.peek()?;
+    self.position += 1;
+    if c == '\n' {
+      self.line += 1;
+      self.column = 1;
+    } else {
+      self.column += 1;
+    }
+    Some(c)
+  }
+
+  fn location(&self) -> (usize, usize) {
+    (self.line, self.column)
+  }
+
+  fn error<Message>(&self, message: Message) -> Error
+  where
+    Message: Into<String>,
+  {
+    self.error_at(self.location(), message)
+  }
+
+  fn error_at<Message>(&self, (line, column): (usize, usize), message: Message) -> Error
+  where
+    Message: Into<String>,
+  {
+    ErrorKind::Syntax(line, column, message.into()).into()
+  }
+
+  fn unexpected(&self) -> Error {
+    match self.peek() {
+      Some(c) => self.error(format!("unexpected character {:?}", c)),
+      None => self.error("unexpected end of input"),
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> Result<()> {
+    if self.peek() == Some(expected) {
+      self.next();
+      Ok(())
+    } else {
+      Err(self.error(format!("expected {:?}", expected)))
+    }
+  }
+
+  /// Skips whitespace and comments.
+  fn skip_layout(&mut self) {
+    while let Some(c) = self.peek() {
+      if c == '%' {
+        while self.peek().map_or(false, |c| c != '\n') {
+          self.next();
+        }
+      } else if c.is_whitespace() {
+        self.next();
+      } else {
+        break;
+      }
+    }
+  }
+
+  /// Consumes the dot that terminates a term, which must be followed by whitespace, a comment or
+  /// the end of the input.
+  fn dot(&mut self) -> Result<()> {
+    self.expect('.')?;
+    match self.peek() {
+      Some(c) if !c.is_whitespace() && c != '%' => {
+        Err(self.error("expected whitespace after the terminating dot"))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  fn term(&mut self) -> Result<Term> {
+    self.skip_layout();
+    match self.peek() {
+      Some('{') => {
+        self.next();
+        let elements = self.elements('}')?;
+        Ok(Tuple(elements.into_boxed_slice()).into())
+      }
+      Some('[') => {
+        self.next();
+        self.list()
+      }
+      Some('#') => {
+        self.next();
+        self.expect('{')?;
+        self.map()
+      }
+      Some('<') if self.peek_at(1) == Some('<') => {
+        self.next();
+        self.next();
+        self.binary()
+      }
+      Some('<') => {
+        self.next();
+        self.pid()
+      }
+      Some('"') => {
+        let chars = self.string()?;
+        if chars.is_empty() {
+          return Ok(Term::Nil);
+        }
+        let elements = chars.into_iter().map(|c| Term::Integer(c as i32));
+        Ok(List::new(elements.collect::<Vec<_>>()).into())
+      }
+      Some('\'') => self.quoted_atom(),
+      Some(c) if is_lowercase_start(c) => self.atom(),
+      Some('$') | Some('0'..='9') | Some('-') | Some('+') => self.number(),
+      _ => Err(self.unexpected()),
+    }
+  }
+
+  /// Parses the elements of a tuple after its opening brace, up to and including `close`.
+  fn elements(&mut self, close: char) -> Result<Vec<Term>> {
+    let mut elements = Vec::new();
+    self.skip_layout();
+    if self.peek() == Some(close) {
+      self.next();
+      return Ok(elements);
+    }
+    loop {
+      elements.push(self.term()?);
+      self.skip_layout();
+      match self.peek() {
+        Some(',') => {
+          self.next();
+        }
+        Some(c) if c == close => {
+          self.next();
+          return Ok(elements);
+        }
+        _ => return Err(self.error(format!("expected ',' or {:?}", close))),
+      }
+    }
+  }
+
+  fn list(&mut self) -> Result<Term> {
+    let mut elements = Vec::new();
+    self.skip_layout();
+    if self.peek() == Some(']') {
+      self.next();
+      return Ok(Term::Nil);
+    }
+    loop {
+      elements.push(self.term()?);
+      self.skip_layout();
+      match self.peek() {
+        Some(',') => {
+          self.next();
+        }
+        Some('|') => {
+          self.next();
+          let tail = self.term()?;
+          self.skip_layout();
+          self.expect(']')?;
+          return Ok(List::improper(elements, tail).into());
+        }
+        Some(']') => {
+          self.next();
+          return Ok(List::new(elements).into());
+        }
+        _ => return Err(self.error("expected ',', '|' or ']'")),
+      }
+    }
+  }
+
+  fn map(&mut self) -> Result<Term> {
+    let mut entries = Vec::new();
+    self.skip_layout();
+    if self.peek() == Some('}') {
+      self.next();
+      return Ok(Map(entries.into_boxed_slice()).into());
+    }
+    loop {
+      let key = self.term()?;
+      self.skip_layout();
+      if self.peek() != Some('=') || self.peek_at(1) != Some('>') {
+        return Err(self.error("expected '=>'"));
+      }
+      self.next();
+      self.next();
+      let value = self.term()?;
+      entries.push((key, value));
+      self.skip_layout();
+      match self.peek() {
+        Some(',') => {
+          self.next();
+        }
+        Some('}') => {
+          self.next();
+          return Ok(Map(entries.into_boxed_slice()).into());
+        }
+        _ => return Err(self.error("expected ',' or '}'")),
+      }
+    }
+  }
+
+  /// Parses a binary after its opening `<<`. Segments are strings or integers, truncated to a byte
+  /// unless they have the `/utf8` type.
+  fn binary(&mut self) -> Result<Term> {
+    let mut bytes = Vec::new();
+    self.skip_layout();
+    if self.peek() != Some('>') {
+      loop {
+        self.binary_segment(&mut bytes)?;
+        self.skip_layout();
+        match self.peek() {
+          Some(',') => {
+            self.next();
+            self.skip_layout();
+          }
+          Some('>') => break,
+          _ => return Err(self.error("expected ',' or '>>'")),
+        }
+      }
+    }
+    if self.peek() != Some('>') || self.peek_at(1) != Some('>') {
+      return Err(self.error("expected '>>'"));
+    }
+    self.next();
+    self.next();
+    Ok(Binary(bytes.into_boxed_slice()).into())
+  }
+
+  fn binary_segment(&mut self, bytes: &mut Vec<u8>) -> Result<()> {
+    let values = match self.peek() {
+      Some('"') => self.string()?,
+      Some('$') | Some('0'..='9') | Some('-') | Some('+') => {
+        let start = self.location();
+        match self.number()? {
+          Term::Integer(value) => vec![value as u32],
+          Term::BigInteger(value) => {
+            let low = u32::from(value.digits()[0]);
+            vec![if value.is_negative() {
+              low.wrapping_neg()
+            } else {
+              low
+            }]
+          }
+          _ => return Err(self.error_at(start, "expected an integer segment")),
+        }
+      }
+      _ => return Err(self.unexpected()),
+    };
+
+    if self.peek() != Some('/') {
+      bytes.extend(values.into_iter().map(|value| value as u8));
+      return Ok(());
+    }
+    self.next();
+    let start = self.location();
+    let mut specifier = String::new();
+    while let Some(c) = self.peek().filter(|&c| is_name_char(c)) {
+      specifier.push(c);
+      self.next();
+    }
+    if specifier != "utf8" {
+      return Err(self.error_at(start, "only the utf8 type specifier is supported"));
+    }
+    for value in values {
+      let c = std::char::from_u32(value)
+        .ok_or_else(|| self.error_at(start, format!("{} is not a Unicode code point", value)))?;
+      let mut buffer = [0; 4];
+      bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+    }
+    Ok(())
+  }
+
+  /// Parses a pid after its opening `<`, such as `<0.42.0>`. The first number identifies the node
+  /// in the numbering of the parser, which also gives the node its serial number.
+  fn pid(&mut self) -> Result<Term> {
+    let start = self.location();
+    let node_number = self.decimal()?;
+    self.expect('.')?;
+    let id = self.decimal()?;
+    self.expect('.')?;
+    let serial = self.decimal()?;
+    self.expect('>')?;
+    let node = self
+      .numbering
+      .node(node_number as usize)
+      .ok_or_else(|| self.error_at(start, format!("no node is numbered {}", node_number)))?;
+    Ok(Pid::new(node, id, serial).into())
+  }
+
+  fn decimal(&mut self) -> Result<u32> {
+    let start = self.location();
+    let mut text = String::new();
+    while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+      text.push(c);
+      self.next();
+    }
+    text
+      .parse()
+      .map_err(|_| self.error_at(start, "expected a number from 0 to 4294967295"))
+  }
+
+  fn atom(&mut self) -> Result<Term> {
+    let start = self.location();
+    let mut name = String::new();
+    while let Some(c) = self.peek().filter(|&c| is_name_char(c)) {
+      name.push(c);
+      self.next();
+    }
+    if RESERVED_WORDS.contains(&&*name) {
+      return Err(self.error_at(start, format!("'{}' is a reserved word", name)));
+    }
+    self.make_atom(start, name)
+  }
+
+  fn quoted_atom(&mut self) -> Result<Term> {
+    let start = self.location();
+    let mut name = String::new();
+    for value in self.quoted('\'')? {
+      let c = std::char::from_u32(value)
+        .ok_or_else(|| self.error_at(start, format!("{} is not a Unicode code point", value)))?;
+      name.push(c);
+    }
+    self.make_atom(start, name)
+  }
+
+  fn make_atom(&self, start: (usize, usize), name: String) -> Result<Term> {
+    Atom::new(name)
+      .map(Term::Atom)
+      .map_err(|error| self.error_at(start, error.to_string()))
+  }
+
+  /// Parses a string and the strings directly following it, which are concatenated.
+  fn string(&mut self) -> Result<Vec<u32>> {
+    let mut chars = self.quoted('"')?;
+    loop {
+      self.skip_layout();
+      if self.peek() != Some('"') {
+        return Ok(chars);
+      }
+      chars.extend(self.quoted('"')?);
+    }
+  }
+
+  fn quoted(&mut self, quote: char) -> Result<Vec<u32>> {
+    let start = self.location();
+    self.expect(quote)?;
+    let mut chars = Vec::new();
+    loop {
+      match self.next() {
+        Some(c) if c == quote => return Ok(chars),
+        Some('\\') => chars.push(self.escape()?),
+        Some(c) => chars.push(c as u32),
+        None => return Err(self.error_at(start, format!("unterminated {:?}", quote))),
+      }
+    }
+  }
+
+  /// Parses an escape sequence after its backslash.
+  fn escape(&mut self) -> Result<u32> {
+    let start = self.location();
+    match self.next() {
+      Some(c @ '0'..='7') => {
+        let mut value = c.to_digit(8).unwrap();
+        for _ in 0..2 {
+          match self.peek().and_then(|c| c.to_digit(8)) {
+            Some(digit) => {
+              value = value * 8 + digit;
+              self.next();
+            }
+            None => break,
+          }
+        }
+        Ok(value)
+      }
+      Some('x') => {
+        let braced = self.peek() == Some('{');
+        if braced {
+          self.next();
+        }
+        let mut value = 0u32;
+        let mut count = 0;
+        while let Some(digit) = self.peek().and_then(|c| c.to_digit(16)) {
+          if (!braced && count == 2) || value > 0x0fff_ffff {
+            break;
+          }
+          value = value * 16 + digit;
+          count += 1;
+          self.next();
+        }
+        if count == 0 || (!braced && count != 2) {
+          return Err(self.error_at(start, "expected hexadecimal digits after '\\x'"));
+        }
+        if braced {
+          self.expect('}')?;
+        }
+        Ok(value)
+      }
+      Some('^') => match self.next() {
+        Some(c) => Ok(c as u32 & 0x1f),
+        None => Err(self.unexpected()),
+      },
+      Some('b') => Ok(0x08),
+      Some('d') => Ok(0x7f),
+      Some('e') => Ok(0x1b),
+      Some('f') => Ok(0x0c),
+      Some('n') => Ok(u32::from(b'\n')),
+      Some('r') => Ok(u32::from(b'\r')),
+      Some('s') => Ok(u32::from(b' ')),
+      Some('t') => Ok(u32::from(b'\t')),
+      Some('v') => Ok(0x0b),
+      Some(c) => Ok(c as u32),
+      None => Err(self.unexpected()),
+    }
+  }
+
+  /// Parses an integer, such as `42`, `-1_000`, `16#FF` or `$a`, or a float, such as `1.5e-3`.
+  fn number(&mut self) -> Result<Term> {
+    let start = self.location();
+    let is_negative = match self.peek() {
+      Some('-') => {
+        self.next();
+        true
+      }
+      Some('+') => {
+        self.next();
+        false
+      }
+      _ => false,
+    };
+
+    if self.peek() == Some('$') {
+      self.next();
+      let value = match self.next() {
+        Some('\\') => self.escape()?,
+        Some(c) => c as u32,
+        None => return Err(self.unexpected()),
+      };
+      let value = i64::from(value);
+      return Ok(Term::from(if is_negative { -value } else { value }));
+    }
+
+    let integral = self.digits(10)?;
+    match self.peek() {
+      Some('#') => {
+        self.next();
+        let radix = integral
+          .iter()
+          .try_fold(0u32, |radix, &digit| {
+            radix.checked_mul(10)?.checked_add(u32::from(digit))
+          })
+          .filter(|radix| (2..=36).contains(radix))
+          .ok_or_else(|| self.error_at(start, "the base must be between 2 and 36"))?;
+        let digits = self.digits(radix)?;
+        Ok(BigInteger::from_radix(is_negative, &digits, radix as u8).into())
+      }
+      Some('.') if self.peek_at(1).map_or(false, |c| c.is_ascii_digit()) => {
+        self.next();
+        let fractional = self.digits(10)?;
+        let mut text = format!(
+          "{}{}.{}",
+          if is_negative { "-" } else { "" },
+          digits_to_string(&integral),
+          digits_to_string(&fractional),
+        );
+        if let Some('e') | Some('E') = self.peek() {
+          self.next();
+          text.push('e');
+          if let Some(sign @ '-') | Some(sign @ '+') = self.peek() {
+            self.next();
+            text.push(sign);
+          }
+          text.push_str(&digits_to_string(&self.digits(10)?));
+        }
+        let value = text.parse::<f64>().unwrap();
+        if !value.is_finite() {
+          return Err(self.error_at(start, "the float is out of range"));
+        }
+        Ok(Term::Float(value))
+      }
+      _ => Ok(BigInteger::from_radix(is_negative, &integral, 10).into()),
+    }
+  }
+
+  /// Parses digits in `radix`, which may be separated by underscores, as in `1_000`.
+  fn digits(&mut self, radix: u32) -> Result<Vec<u8>> {
+    let mut digits = Vec::new();
+    while let Some(digit) = self.peek().and_then(|c| c.to_digit(radix)) {
+      digits.push(digit as u8);
+      self.next();
+      if self.peek() == Some('_') && self.peek_at(1).and_then(|c| c.to_digit(radix)).is_some() {
+        self.next();
+      }
+    }
+    if digits.is_empty() {
+      return Err(self.error(format!("expected a digit in base {}", radix)));
+    }
+    Ok(digits)
+  }
+}
+
+fn digits_to_string(digits: &[u8]) -> String {
+  digits
+    .iter()
+    .map(|&digit| std::char::from_digit(u32::from(digit), 10).unwrap())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::node;
+
+  fn parse(text: &str) -> Term {
+    text
+      .parse()
+      .unwrap_or_else(|error| panic!("{:?}: {}", text, error))
+  }
+
+  /// Returns the line, column and message of the error that parsing `text` fails with.
+  fn syntax_error(text: &str) -> (usize, usize, String) {
+    match text.parse::<Term>() {
+      Err(Error(ErrorKind::Syntax(line, column, message), _)) => (line, column, message),
+      result => panic!("{:?}: {:?}", text, result),
+    }
+  }
+
+  fn atom(name: &str) -> Term {
+    Term::Atom(Atom::new(name).unwrap())
+  }
+
+  fn chars(text: &str) -> Term {
+    List::new(
+      text
+        .chars()
+        .map(|c| Term::Integer(c as i32))
+        .collect::<Vec<_>>(),
+    )
+    .into()
+  }
+
+  #[test]
+  fn atoms_are_parsed() {
+    assert_eq!(parse("ok"), atom("ok"));
+    assert_eq!(parse("node@host_1"), atom("node@host_1"));
+    assert_eq!(parse("'hello world'"), atom("hello world"));
+    assert_eq!(parse(r"'it\'s\n'"), atom("it's\n"));
+    assert_eq!(parse("'caf\\x{E9}'"), atom("café"));
+    assert_eq!(parse("'Ok'"), atom("Ok"));
+  }
+
+  #[test]
+  fn numbers_are_parsed() {
+    assert_eq!(parse("42"), Term::Integer(42));
+    assert_eq!(parse("-1_000"), Term::Integer(-1000));
+    assert_eq!(parse("+7"), Term::Integer(7));
+    assert_eq!(parse("16#FF"), Term::Integer(255));
+    assert_eq!(parse("-2#101"), Term::Integer(-5));
+    assert_eq!(parse("36#zz"), Term::Integer(1295));
+    assert_eq!(parse("$a"), Term::Integer(97));
+    assert_eq!(parse("$\\n"), Term::Integer(10));
+    assert_eq!(parse("$\\^A"), Term::Integer(1));
+    assert_eq!(parse("$\\101"), Term::Integer(65));
+    assert_eq!(parse("-$a"), Term::Integer(-97));
+    assert_eq!(parse("4294967296"), Term::from(1i64 << 32));
+    assert_eq!(parse("-16#8000000000000000"), Term::from(i64::min_value()));
+    assert_eq!(parse("1.5"), Term::Float(1.5));
+    assert_eq!(parse("-2.5e-3"), Term::Float(-0.0025));
+    assert_eq!(parse("1.0E+2"), Term::Float(100.0));
+  }
+
+  #[test]
+  fn strings_are_parsed() {
+    assert_eq!(parse("\"\""), Term::Nil);
+    assert_eq!(parse("\"abc\""), chars("abc"));
+    assert_eq!(parse(r#""a\"b\tc""#), chars("a\"b\tc"));
+    assert_eq!(parse("\"\\x41\\x{3b1}\""), chars("Aα"));
+    // Adjacent strings are concatenated, even across lines and comments.
+    assert_eq!(parse("\"ab\" \"c\"\n% comment\n\"d\""), chars("abcd"));
+    assert_eq!(parse("\"\" \"\""), Term::Nil);
+  }
+
+  #[test]
+  fn compound_terms_are_parsed() {
+    let tuple = |elements: Vec<Term>| Term::from(Tuple(elements.into_boxed_slice()));
+    assert_eq!(parse("{}"), tuple(vec![]));
+    assert_eq!(
+      parse("{ ok , 1 }."),
+      tuple(vec![atom("ok"), Term::Integer(1)])
+    );
+    assert_eq!(parse("[]"), Term::Nil);
+    assert_eq!(
+      parse("[1, 2]"),
+      List::new(vec![Term::Integer(1), Term::Integer(2)]).into()
+    );
+    assert_eq!(
+      parse("[a | b]"),
+      List::improper(vec![atom("a")], atom("b")).into()
+    );
+    assert_eq!(parse("#{}"), Map(Box::new([])).into());
+    assert_eq!(
+      parse("#{a => 1, \"b\" => [c]}"),
+      Map(
+        vec![
+          (atom("a"), Term::Integer(1)),
+          (chars("b"), List::new(vec![atom("c")]).into()),
+        ]
+        .into_boxed_slice()
+      )
+      .into()
+    );
+    assert_eq!(
+      parse("<0.42.3>"),
+      Pid::new(
+        Node::new(Atom::new(node::NOT_DISTRIBUTED_NODE).unwrap(), 0),
+        42,
+        3
+      )
+      .into()
+    );
+  }
+
+  #[test]
+  fn pids_are_read_with_a_numbering() {
+    let node = |name: &str, creation| Node::new(Atom::new(name).unwrap(), creation);
+    let numbering = NodeNumbering::new(node("local@parse", 7));
+    let pids = [
+      Pid::new(node("first@parse", 1), 1, 2),
+      Pid::new(node("second@parse", 2), 3, 4),
+      Pid::new(node("first@parse", 3), 5, 6),
+    ];
+    let text = Term::from(List::new(
+      pids.iter().cloned().map(Term::from).collect::<Vec<_>>(),
+    ))
+    .display_with(&numbering)
+    .to_string();
+    assert_eq!(text, "[<1.1.2>,<2.3.4>,<3.5.6>]");
+    match Term::parse_with("<3.5.6>", &numbering).unwrap() {
+      Term::Pid(pid) => {
+        assert_eq!(pid, pids[2]);
+        assert_eq!(pid.node.serial_number(), 3);
+      }
+      term => panic!("{:?}", term),
+    }
+    assert_eq!(
+      Term::parse_with("<0.1.2>", &numbering).unwrap(),
+      Pid::new(node("local@parse", 7), 1, 2).into()
+    );
+    assert_eq!(
+      consult_with("<2.3.4>.\n<1.1.2>.", &numbering).unwrap(),
+      [pids[1].clone().into(), pids[0].clone().into()]
+    );
+    // The default numbering only numbers the local node.
+    assert_eq!(
+      syntax_error("<3.5.6>"),
+      (1, 2, "no node is numbered 3".to_owned())
+    );
+  }
+
+  #[test]
+  fn binaries_are_parsed() {
+    let binary = |bytes: &[u8]| Term::from(Binary(bytes.to_vec().into_boxed_slice()));
+    assert_eq!(parse("<<>>"), binary(b""));
+    assert_eq!(parse("<< >>"), binary(b""));
+    assert_eq!(parse("<<1, 255, 256, -1>>"), binary(&[1, 255, 0, 255]));
+    assert_eq!(parse("<<\"abc\", $d>>"), binary(b"abcd"));
+    assert_eq!(parse("<<\"é\"/utf8, 960/utf8>>"), binary("éπ".as_bytes()));
+    assert_eq!(parse("<<\"é\">>"), binary(&[0xe9]));
+    assert_eq!(parse("<<16#1FF>>"), binary(&[0xff]));
+  }
+
+  #[test]
+  fn files_are_consulted() {
+    let text = "%% Settings.\n{port, 8080}.\n\n[a,\n b]. % Trailing comment.\n";
+    assert_eq!(
+      consult(text).unwrap(),
+      [
+        Tuple(vec![atom("port"), Term::Integer(8080)].into_boxed_slice()).into(),
+        List::new(vec![atom("a"), atom("b")]).into(),
+      ]
+    );
+    assert!(consult("").unwrap().is_empty());
+    assert!(consult("  % Nothing.\n").unwrap().is_empty());
+
+    let path = std::env::temp_dir().join(format!("erlang-cnode-consult-{}", std::process::id()));
+    fs::write(&path, text).unwrap();
+    let terms = consult_file(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(terms.unwrap().len(), 2);
+    assert!(consult_file(&path).is_err());
+
+    match consult("ok.\nok") {
+      Err(Error(ErrorKind::Syntax(2, 3, _), _)) => (),
+      result => panic!("{:?}", result),
+    }
+  }
+
+  #[test]
+  fn syntax_errors_are_located() {
+    let cases = vec![
+      (")", 1, 1, "unexpected character ')'"),
+      ("", 1, 1, "unexpected end of input"),
+      ("ok ok", 1, 4, "expected the end of the input"),
+      (
+        "ok.ok",
+        1,
+        4,
+        "expected whitespace after the terminating dot",
+      ),
+      ("{a b}", 1, 4, "expected ',' or '}'"),
+      ("{a,\n  b c}", 2, 5, "expected ',' or '}'"),
+      ("[a b]", 1, 4, "expected ',', '|' or ']'"),
+      ("[a | b c]", 1, 8, "expected ']'"),
+      ("#[]", 1, 2, "expected '{'"),
+      ("#{a b}", 1, 5, "expected '=>'"),
+      ("#{a => b c}", 1, 10, "expected ',' or '}'"),
+      ("<<1 2>>", 1, 5, "expected ',' or '>>'"),
+      ("<<1>", 1, 4, "expected '>>'"),
+      ("<<a>>", 1, 3, "unexpected character 'a'"),
+      ("<<1.5>>", 1, 3, "expected an integer segment"),
+      (
+        "<<1/float>>",
+        1,
+        5,
+        "only the utf8 type specifier is supported",
+      ),
+      (
+        "<<16#110000/utf8>>",
+        1,
+        13,
+        "1114112 is not a Unicode code point",
+      ),
+      ("<999.2.3>", 1, 2, "no node is numbered 999"),
+      ("<0.x.1>", 1, 4, "expected a number from 0 to 4294967295"),
+      ("<0.1.1]", 1, 7, "expected '>'"),
+      ("receive", 1, 1, "'receive' is a reserved word"),
+      ("'abc", 1, 1, "unterminated '\\''"),
+      ("\"abc", 1, 1, "unterminated '\"'"),
+      ("\"\\xZ\"", 1, 3, "expected hexadecimal digits after '\\x'"),
+      ("\"\\x{41\"", 1, 7, "expected '}'"),
+      ("'\\x{110000}'", 1, 1, "1114112 is not a Unicode code point"),
+      ("37#1", 1, 1, "the base must be between 2 and 36"),
+      ("16#G", 1, 4, "expected a digit in base 16"),
+      ("-", 1, 2, "expected a digit in base 10"),
+      ("1.0e999", 1, 1, "the float is out of range"),
+    ];
+    for (text, line, column, message) in cases {
+      assert_eq!(
+        syntax_error(text),
+        (line, column, message.to_owned()),
+        "{:?}",
+        text
+      );
+    }
+
+    let (line, column, _) = syntax_error(&"a".repeat(256));
+    assert_eq!((line, column), (1, 1));
+  }
+}
@@ -1,27 +1,38 @@
-use crate::{err::*, ty::*};
+use crate::ty::*;
+use std::{fmt, result};
 
 impl Pid {
-  /// Creates a new `Id`.
+  /// Creates a new `Pid`.
   ///
-  /// Even though the protocol reserves 4 bytes for it, only the 15 lower bits are significant.
-  /// This function returns a new `Id` if the given integer in within the range of a 15 bits
-  /// integer and an error otherwise.
-  pub fn new(node: Node, id: u32, serial: u32) -> Result<Self> {
-    const ID_MAX: u32 = (1 << 15) - 1;
-    const SERIAL_MAX: u32 = (1 << 13) - 1;
+  /// Legacy peers only consider the 15 lower bits of the id and the 13 lower bits of the serial
+  /// significant, but nodes since OTP 23 use the whole 32 bits of both. The ids are stored in full
+  /// and only checked against the legacy ranges when encoding for a legacy peer.
+  pub fn new(node: Node, id: u32, serial: u32) -> Self {
+    Pid { node, id, serial }
+  }
+
+  pub fn id(&self) -> u32 {
+    self.id
+  }
 
-    if id <= ID_MAX && serial <= SERIAL_MAX {
-      Ok(Pid {
-        node,
-        id: id as u16,
-        serial: serial as u16,
-      })
-    } else {
-      Err(ErrorKind::PidOutOfRange(node, id, serial).into())
-    }
+  pub fn serial(&self) -> u32 {
+    self.serial
   }
 
-  pub fn id(self) -> u32 {
-    self.id.into()
+  /// Writes the pid like the runtime, as in `<5.42.0>`, where 5 is the number of its node.
+  pub(crate) fn write_numbered(
+    &self,
+    formatter: &mut fmt::Formatter,
+    node_number: usize,
+  ) -> result::Result<(), fmt::Error> {
+    write!(formatter, "<{}.{}.{}>", node_number, self.id, self.serial)
+  }
+}
+
+/// Formats the pid like a local pid, as in `<0.42.0>`, since node numbers are only meaningful
+/// within a numbering. See `Term::display_with`.
+impl fmt::Display for Pid {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    self.write_numbered(formatter, 0)
   }
 }
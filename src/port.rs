@@ -0,0 +1,28 @@
+use crate::ty::*;
+use std::{fmt, result};
+
+impl Port {
+  pub fn new(node: Node, id: u64) -> Self {
+    Port { node, id }
+  }
+
+  pub fn id(&self) -> u64 {
+    self.id
+  }
+
+  /// Writes the port like the runtime, as in `#Port<5.42>`, where 5 is the number of its node.
+  pub(crate) fn write_numbered(
+    &self,
+    formatter: &mut fmt::Formatter,
+    node_number: usize,
+  ) -> result::Result<(), fmt::Error> {
+    write!(formatter, "#Port<{}.{}>", node_number, self.id)
+  }
+}
+
+/// Formats the port like a local port, as in `#Port<0.42>`. See `Term::display_with`.
+impl fmt::Display for Port {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    self.write_numbered(formatter, 0)
+  }
+}
@@ -0,0 +1,526 @@
+use crate::{atom::write_escaped_char, order::canonical, ty::*};
+use std::{fmt, result};
+
+/// The line length that `io:format("~p")` uses by default.
+const DEFAULT_WIDTH: usize = 80;
+
+impl Term {
+  /// Returns a value that formats the term like `io:format("~p")`, breaking it across lines so that
+  /// it fits in `width` columns where possible.
+  pub fn pretty(&self, width: usize) -> Pretty<'_> {
+    Pretty {
+      term: self,
+      width,
+      numbering: None,
+    }
+  }
+
+  /// Returns a value that formats the term like `Display`, except that the nodes of its pids, ports
+  /// and references are numbered with `numbering`, as in `<5.42.0>`, instead of all being written
+  /// like the local node.
+  pub fn display_with<'term>(&'term self, numbering: &'term NodeNumbering) -> NumberedTerm<'term> {
+    NumberedTerm {
+      term: self,
+      numbering,
+    }
+  }
+}
+
+impl<'term> Pretty<'term> {
+  /// Numbers the nodes of pids, ports and references with `numbering`, like `Term::display_with`.
+  pub fn with_numbering(self, numbering: &'term NodeNumbering) -> Self {
+    Pretty {
+      numbering: Some(numbering),
+      ..self
+    }
+  }
+}
+
+/// Formats the term on one line like `io:format("~p")` does, as in `{ok,<0.42.0>,"abc"}`. Pids,
+/// ports and references are written like local ones, see `Term::display_with`.
+///
+/// The alternate flag, as in `{:#}`, formats it like `Term::pretty` with a width of 80 columns.
+impl fmt::Display for Term {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    if formatter.alternate() {
+      fmt::Display::fmt(&self.pretty(DEFAULT_WIDTH), formatter)
+    } else {
+      write_flat(formatter, self, None)
+    }
+  }
+}
+
+/// The alternate flag, as in `{:#}`, formats the term like `Term::pretty` with a width of 80
+/// columns.
+impl<'term> fmt::Display for NumberedTerm<'term> {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    if formatter.alternate() {
+      let pretty = self
+        .term
+        .pretty(DEFAULT_WIDTH)
+        .with_numbering(self.numbering);
+      fmt::Display::fmt(&pretty, formatter)
+    } else {
+      write_flat(formatter, self.term, Some(self.numbering))
+    }
+  }
+}
+
+impl<'term> fmt::Display for Pretty<'term> {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    write_pretty(formatter, self.term, self.numbering, self.width, 0)
+  }
+}
+
+fn node_number(numbering: Option<&NodeNumbering>, node: &Node) -> usize {
+  numbering.map_or(0, |numbering| numbering.number(node))
+}
+
+fn write_flat(
+  formatter: &mut fmt::Formatter,
+  term: &Term,
+  numbering: Option<&NodeNumbering>,
+) -> result::Result<(), fmt::Error> {
+  match canonical(term) {
+    Term::Nil => formatter.write_str("[]"),
+    Term::Integer(value) => write!(formatter, "{}", value),
+    Term::BigInteger(value) => write!(formatter, "{}", value),
+    Term::Float(value) => write_float(formatter, *value),
+    Term::Atom(atom) => write!(formatter, "{}", atom),
+    Term::Pid(pid) => pid.write_numbered(formatter, node_number(numbering, &pid.node)),
+    Term::Port(port) => port.write_numbered(formatter, node_number(numbering, &port.node)),
+    Term::Reference(reference) => {
+      reference.write_numbered(formatter, node_number(numbering, &reference.node))
+    }
+    Term::Tuple(Tuple(elements)) => {
+      formatter.write_str("{")?;
+      write_flat_elements(formatter, elements.iter(), numbering)?;
+      formatter.write_str("}")
+    }
+    Term::List(list) => match printable_chars(list) {
+      Some(chars) => write_string(formatter, chars),
+      None => {
+        formatter.write_str("[")?;
+        write_flat_elements(formatter, list, numbering)?;
+        if !list.is_proper() {
+          formatter.write_str("|")?;
+          write_flat(formatter, list.tail(), numbering)?;
+        }
+        formatter.write_str("]")
+      }
+    },
+    Term::Binary(Binary(bytes)) => write_binary(formatter, bytes, None),
+    Term::BitBinary(bit_binary) => {
+      let whole_bytes = &bit_binary.bytes()[..bit_binary.bytes().len() - 1];
+      let trailing = (bit_binary.trailing_value(), bit_binary.trailing_bits());
+      write_binary(formatter, whole_bytes, Some(trailing))
+    }
+    Term::Export(export) => write!(formatter, "{}", export),
+    Term::Fun(fun) => write!(formatter, "{}", fun),
+    Term::Map(Map(entries)) => {
+      formatter.write_str("#{")?;
+      for (index, (key, value)) in entries.iter().enumerate() {
+        if index > 0 {
+          formatter.write_str(",")?;
+        }
+        write_flat(formatter, key, numbering)?;
+        formatter.write_str(" => ")?;
+        write_flat(formatter, value, numbering)?;
+      }
+      formatter.write_str("}")
+    }
+  }
+}
+
+fn write_flat_elements<'term, Elements>(
+  formatter: &mut fmt::Formatter,
+  elements: Elements,
+  numbering: Option<&NodeNumbering>,
+) -> result::Result<(), fmt::Error>
+where
+  Elements: IntoIterator<Item = &'term Term>,
+{
+  for (index, element) in elements.into_iter().enumerate() {
+    if index > 0 {
+      formatter.write_str(",")?;
+    }
+    write_flat(formatter, element, numbering)?;
+  }
+  Ok(())
+}
+
+/// Formats a term like `write_flat`, to measure it or to write it to a string.
+struct Flat<'term> {
+  term: &'term Term,
+  numbering: Option<&'term NodeNumbering>,
+}
+
+impl<'term> fmt::Display for Flat<'term> {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    write_flat(formatter, self.term, self.numbering)
+  }
+}
+
+/// Counts the characters written to it, and fails as soon as there are more than `remaining`.
+struct BoundedCounter {
+  remaining: usize,
+}
+
+impl fmt::Write for BoundedCounter {
+  fn write_str(&mut self, text: &str) -> result::Result<(), fmt::Error> {
+    let len = text.chars().count();
+    if len > self.remaining {
+      return Err(fmt::Error);
+    }
+    self.remaining -= len;
+    Ok(())
+  }
+}
+
+/// Returns whether the term fits in `columns` when written flat. Only writes as much of it as fits.
+fn fits(term: &Term, numbering: Option<&NodeNumbering>, columns: usize) -> bool {
+  let mut counter = BoundedCounter { remaining: columns };
+  fmt::write(&mut counter, format_args!("{}", Flat { term, numbering })).is_ok()
+}
+
+/// Writes the term flat if it fits in the remaining columns. Otherwise, breaks tuples, lists and
+/// maps after each element and aligns the elements after the opening delimiter.
+fn write_pretty(
+  formatter: &mut fmt::Formatter,
+  term: &Term,
+  numbering: Option<&NodeNumbering>,
+  width: usize,
+  column: usize,
+) -> result::Result<(), fmt::Error> {
+  if fits(term, numbering, width.saturating_sub(column)) {
+    return write_flat(formatter, term, numbering);
+  }
+
+  match canonical(term) {
+    Term::Tuple(Tuple(elements)) if !elements.is_empty() => {
+      formatter.write_str("{")?;
+      write_pretty_elements(formatter, elements.iter(), numbering, width, column + 1)?;
+      formatter.write_str("}")
+    }
+    Term::List(list) if printable_chars(list).is_none() => {
+      formatter.write_str("[")?;
+      write_pretty_elements(formatter, list, numbering, width, column + 1)?;
+      if !list.is_proper() {
+        formatter.write_str("|")?;
+        write_pretty(formatter, list.tail(), numbering, width, column + 1)?;
+      }
+      formatter.write_str("]")
+    }
+    Term::Map(Map(entries)) if !entries.is_empty() => {
+      formatter.write_str("#{")?;
+      for (index, (key, value)) in entries.iter().enumerate() {
+        if index > 0 {
+          write_line_break(formatter, column + 2)?;
+        }
+        let key = Flat {
+          term: key,
+          numbering,
+        }
+        .to_string();
+        formatter.write_str(&key)?;
+        formatter.write_str(" => ")?;
+        write_pretty(
+          formatter,
+          value,
+          numbering,
+          width,
+          column + 2 + key.chars().count() + 4,
+        )?;
+      }
+      formatter.write_str("}")
+    }
+    _ => write_flat(formatter, term, numbering),
+  }
+}
+
+fn write_pretty_elements<'term, Elements>(
+  formatter: &mut fmt::Formatter,
+  elements: Elements,
+  numbering: Option<&NodeNumbering>,
+  width: usize,
+  column: usize,
+) -> result::Result<(), fmt::Error>
+where
+  Elements: IntoIterator<Item = &'term Term>,
+{
+  for (index, element) in elements.into_iter().enumerate() {
+    if index > 0 {
+      write_line_break(formatter, column)?;
+    }
+    write_pretty(formatter, element, numbering, width, column)?;
+  }
+  Ok(())
+}
+
+fn write_line_break(
+  formatter: &mut fmt::Formatter,
+  column: usize,
+) -> result::Result<(), fmt::Error> {
+  write!(formatter, ",\n{:1$}", "", column)
+}
+
+/// Writes a float with the fewest digits that read back to the same value, choosing between the
+/// fixed and the exponent notations like `io_lib_format:fwrite_g/1`.
+fn write_float(formatter: &mut fmt::Formatter, value: f64) -> result::Result<(), fmt::Error> {
+  if value == 0.0 {
+    return formatter.write_str(if value.is_sign_negative() {
+      "-0.0"
+    } else {
+      "0.0"
+    });
+  }
+  if !value.is_finite() {
+    return write!(formatter, "{}", value);
+  }
+
+  // The shortest digits of the value, such that it is `0.{digits} * 10^place`.
+  let scientific = format!("{:e}", value.abs());
+  let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+  let digits = mantissa.replace('.', "");
+  let place = exponent[1..].parse::<i32>().unwrap() + 1;
+  let len = digits.len() as i32;
+
+  if value < 0.0 {
+    formatter.write_str("-")?;
+  }
+  if place == 0 {
+    return write!(formatter, "0.{}", digits);
+  }
+  if place > 0 && place < len {
+    let (integral, fractional) = digits.split_at(place as usize);
+    return write!(formatter, "{}.{}", integral, fractional);
+  }
+
+  let exponent = (place - 1).to_string();
+  let exponent_dot = if len == 1 { 2 } else { 1 };
+  let exponent_cost = exponent.len() as i32 + 1 + exponent_dot;
+  if place < 0 && 2 - place <= exponent_cost {
+    write!(formatter, "0.{:0>1$}", digits, (len - place) as usize)
+  } else if place >= len && place - len + 2 <= exponent_cost {
+    write!(formatter, "{:0<1$}.0", digits, place as usize)
+  } else {
+    let (first, rest) = digits.split_at(1);
+    let rest = if rest.is_empty() { "0" } else { rest };
+    write!(formatter, "{}.{}e{}", first, rest, exponent)
+  }
+}
+
+/// Returns the characters of a list if `io_lib:printable_list/1` considers it a string, that is if
+/// it is a proper list of printable Latin-1 characters.
+fn printable_chars(list: &List) -> Option<String> {
+  if !list.is_proper() {
+    return None;
+  }
+  list
+    .iter()
+    .map(|element| match element {
+      Term::Integer(value) => num_traits::NumCast::from(*value)
+        .filter(|&byte| is_printable(byte))
+        .map(char::from),
+      _ => None,
+    })
+    .collect()
+}
+
+fn is_printable(byte: u8) -> bool {
+  match byte {
+    b' '..=b'~' | 0xa0..=0xff => true,
+    b'\n' | b'\r' | b'\t' | 0x0b | 0x08 | 0x0c | 0x1b => true,
+    _ => false,
+  }
+}
+
+fn write_string(formatter: &mut fmt::Formatter, text: String) -> result::Result<(), fmt::Error> {
+  formatter.write_str("\"")?;
+  for c in text.chars() {
+    match c {
+      '"' => formatter.write_str("\\\"")?,
+      _ => write_escaped_char(formatter, c)?,
+    }
+  }
+  formatter.write_str("\"")
+}
+
+/// Writes the bytes of a binary, then the value and the size of the trailing bits of a bitstring, as
+/// in `<<"abc",5:3>>`.
+fn write_binary(
+  formatter: &mut fmt::Formatter,
+  bytes: &[u8],
+  trailing: Option<(u8, u8)>,
+) -> result::Result<(), fmt::Error> {
+  formatter.write_str("<<")?;
+  if bytes.iter().all(|&byte| is_printable(byte)) {
+    if !bytes.is_empty() {
+      write_string(
+        formatter,
+        bytes.iter().map(|&byte| char::from(byte)).collect(),
+      )?;
+    }
+  } else {
+    for (index, byte) in bytes.iter().enumerate() {
+      if index > 0 {
+        formatter.write_str(",")?;
+      }
+      write!(formatter, "{}", byte)?;
+    }
+  }
+  if let Some((value, bits)) = trailing {
+    if !bytes.is_empty() {
+      formatter.write_str(",")?;
+    }
+    write!(formatter, "{}:{}", value, bits)?;
+  }
+  formatter.write_str(">>")
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::ty::*;
+
+  fn atom(name: &str) -> Term {
+    Term::Atom(Atom::new(name).unwrap())
+  }
+
+  fn tuple(elements: Vec<Term>) -> Term {
+    Tuple(elements.into_boxed_slice()).into()
+  }
+
+  #[test]
+  fn terms_are_written_like_io_format() {
+    let chars = |text: &str| {
+      List::new(
+        text
+          .bytes()
+          .map(|byte| Term::Integer(byte.into()))
+          .collect::<Vec<_>>(),
+      )
+      .into()
+    };
+    let cases = vec![
+      (atom("ok"), "ok"),
+      (atom("hello world"), "'hello world'"),
+      (atom("Ok"), "'Ok'"),
+      (atom("receive"), "'receive'"),
+      (atom("it's"), r"'it\'s'"),
+      (Term::Integer(-42), "-42"),
+      (Term::from(1u64 << 40), "1099511627776"),
+      (Term::Float(1.5), "1.5"),
+      (Term::Float(-0.0), "-0.0"),
+      (Term::Float(1e21), "1.0e21"),
+      (Term::Float(0.001), "0.001"),
+      (Term::Nil, "[]"),
+      (chars("say \"hi\"\n"), r#""say \"hi\"\n""#),
+      (
+        List::new(vec![Term::Integer(1), Term::Integer(300)]).into(),
+        "[1,300]",
+      ),
+      (List::improper(vec![atom("a")], atom("b")).into(), "[a|b]"),
+      (Binary(Box::new(*b"abc")).into(), r#"<<"abc">>"#),
+      (Binary(Box::new([0, 255])).into(), "<<0,255>>"),
+      (Binary(Box::new([])).into(), "<<>>"),
+      (tuple(vec![]), "{}"),
+      (tuple(vec![atom("ok"), Term::Integer(1)]), "{ok,1}"),
+      (
+        Map(vec![(atom("a"), Term::Integer(1)), (Term::Integer(2), atom("b"))].into_boxed_slice())
+          .into(),
+        "#{a => 1,2 => b}",
+      ),
+    ];
+    for (term, expected) in cases {
+      assert_eq!(term.to_string(), expected);
+    }
+  }
+
+  #[test]
+  fn pretty_terms_are_broken_to_fit() {
+    let term = tuple(vec![
+      atom("reply"),
+      List::new(vec![atom("first"), atom("second")]).into(),
+      Map(vec![(atom("key"), tuple(vec![atom("value"), Term::Integer(1)]))].into_boxed_slice())
+        .into(),
+    ]);
+    assert_eq!(
+      term.pretty(80).to_string(),
+      "{reply,[first,second],#{key => {value,1}}}"
+    );
+    assert_eq!(
+      term.pretty(24).to_string(),
+      "{reply,\n [first,second],\n #{key => {value,1}}}"
+    );
+    assert_eq!(
+      term.pretty(16).to_string(),
+      "{reply,\n [first,second],\n #{key => {value,\n           1}}}"
+    );
+    assert_eq!(format!("{:#}", term), term.pretty(80).to_string());
+  }
+
+  #[test]
+  fn bitstrings_are_written_with_their_size() {
+    let bits =
+      |bytes: Vec<u8>, trailing_bits| Term::from(BitBinary::new(bytes, trailing_bits).unwrap());
+    assert_eq!(bits(vec![0xa0], 3).to_string(), "<<5:3>>");
+    assert_eq!(bits(vec![1, 2, 0x40], 2).to_string(), "<<1,2,1:2>>");
+  }
+
+  fn pid(node_name: &str, creation: u32, id: u32) -> Term {
+    Pid::new(Node::new(Atom::new(node_name).unwrap(), creation), id, 0).into()
+  }
+
+  #[test]
+  fn pids_are_written_like_local_ones() {
+    assert_eq!(pid("other@print", 1, 42).to_string(), "<0.42.0>");
+  }
+
+  #[test]
+  fn pids_are_numbered_by_node() {
+    let numbering = NodeNumbering::new(Node::new(Atom::new("local@print").unwrap(), 1));
+    let numbered = |term: Term| term.display_with(&numbering).to_string();
+    assert_eq!(numbered(pid("local@print", 1, 42)), "<0.42.0>");
+    assert_eq!(numbered(pid("first@print", 1, 42)), "<1.42.0>");
+    assert_eq!(numbered(pid("second@print", 1, 42)), "<2.42.0>");
+    assert_eq!(numbered(pid("first@print", 1, 43)), "<1.43.0>");
+    assert_eq!(numbered(pid("local@print", 2, 42)), "<3.42.0>");
+    assert_eq!(
+      numbering.node(3),
+      Some(Node::new(Atom::new("local@print").unwrap(), 2))
+    );
+
+    let term = tuple(vec![pid("second@print", 1, 7), pid("third@print", 1, 8)]);
+    assert_eq!(numbered(term.clone()), "{<2.7.0>,<4.8.0>}");
+    assert_eq!(
+      term.pretty(10).with_numbering(&numbering).to_string(),
+      "{<2.7.0>,\n <4.8.0>}"
+    );
+  }
+
+  #[test]
+  fn deeply_nested_terms_are_pretty_printed() {
+    let mut term = atom("leaf");
+    for _ in 0..200 {
+      term = tuple(vec![term, Term::Integer(1)]);
+    }
+    let pretty = term.pretty(80).to_string();
+    assert!(pretty
+      .lines()
+      .all(|line| line.ends_with(',') || line.ends_with('}')));
+    let flattened = pretty.split_whitespace().collect::<String>();
+    assert_eq!(flattened, term.to_string());
+  }
+
+  #[test]
+  fn atoms_are_escaped_like_latin1_output() {
+    let atom = |name: &str| Term::from(Atom::new(name).unwrap()).to_string();
+    assert_eq!(atom("café"), "café");
+    assert_eq!(
+      atom("привет"),
+      r"'\x{43F}\x{440}\x{438}\x{432}\x{435}\x{442}'"
+    );
+    assert_eq!(atom("a\u{85}b"), r"'a\205b'");
+    assert_eq!(atom("a\u{1}"), r"'a\^A'");
+  }
+}
@@ -1,6 +1,20 @@
-use crate::{err::*, ext, read, ty::*};
+use crate::{err::*, ext, hash, read, ty::*, write};
 use ei_sys as ei;
-use std::str;
+use std::{
+  collections::{HashMap, HashSet, VecDeque},
+  io,
+};
+
+const PASS_THROUGH: u8 = b'p';
+const DIST_FRAG_HEADER: u8 = 69;
+const DIST_FRAG_CONT: u8 = 70;
+
+/// The number of slots of an atom cache that the runtime uses. The cache has 8 segments of 256
+/// atoms, but the runtime picks slots modulo the largest prime below 2048.
+const USED_ATOM_CACHE_SIZE: u32 = 2039;
+
+/// The largest number of atoms that the distribution header of a message can refer to.
+const MAX_ATOM_CACHE_REFERENCES: usize = 255;
 
 struct AtomCacheReferenceFlags {
   is_new_entry: bool,
@@ -26,6 +40,13 @@ impl From<u8> for AtomCacheReferenceFlags {
   }
 }
 
+impl AtomCacheReferenceFlags {
+  fn into_half_byte(self) -> u8 {
+    let new_entry_flag = if self.is_new_entry { 0x8 } else { 0 };
+    new_entry_flag | self.segment_index as u8
+  }
+}
+
 pub fn read_version_magic<'input>(input: &[u8]) -> read::IResult<()> {
   let (input, version) = read::be_u8::<u8>(input)?;
   if version == ei::VERSION_MAGIC {
@@ -35,18 +56,369 @@ pub fn read_version_magic<'input>(input: &[u8]) -> read::IResult<()> {
   }
 }
 
+pub fn write_version_magic<W: io::Write>(output: &mut W) -> Result<()> {
+  write::be_u8(output, ei::VERSION_MAGIC)
+}
+
+pub fn write_send<W: io::Write>(
+  output: &mut W,
+  to: &Pid,
+  term: &Term,
+  options: ext::WriteOptions,
+  atom_cache: Option<&mut AtomCache>,
+  fragmenter: Option<&mut Fragmenter>,
+) -> Result<()> {
+  let control_message = Tuple(
+    vec![
+      Term::Integer(ei::ERL_SEND as i32),
+      Atom::new("")?.into(),
+      to.clone().into(),
+    ]
+    .into_boxed_slice(),
+  );
+  write_control_message(
+    output,
+    &control_message.into(),
+    term,
+    options,
+    atom_cache,
+    fragmenter,
+  )
+}
+
+pub fn write_registered_send<W: io::Write>(
+  output: &mut W,
+  from: &Pid,
+  to: &Atom,
+  term: &Term,
+  options: ext::WriteOptions,
+  atom_cache: Option<&mut AtomCache>,
+  fragmenter: Option<&mut Fragmenter>,
+) -> Result<()> {
+  let control_message = Tuple(
+    vec![
+      Term::Integer(ei::ERL_REG_SEND as i32),
+      from.clone().into(),
+      Atom::new("")?.into(),
+      to.clone().into(),
+    ]
+    .into_boxed_slice(),
+  );
+  write_control_message(
+    output,
+    &control_message.into(),
+    term,
+    options,
+    atom_cache,
+    fragmenter,
+  )
+}
+
+/// Writes a control message and its payload, behind a distribution header if there is an outgoing
+/// atom cache. Fragments the message if there is a fragmenter, which needs a distribution header.
+fn write_control_message<W: io::Write>(
+  output: &mut W,
+  control_message: &Term,
+  term: &Term,
+  options: ext::WriteOptions,
+  atom_cache: Option<&mut AtomCache>,
+  fragmenter: Option<&mut Fragmenter>,
+) -> Result<()> {
+  match atom_cache {
+    Some(atom_cache) => write_with_distribution_header(
+      output,
+      control_message,
+      term,
+      options,
+      atom_cache,
+      fragmenter,
+    ),
+    None => write_pass_through(output, control_message, term, options),
+  }
+}
+
+/// Writes a control message and its payload as a single pass through packet, the format used by
+/// peers that did not negotiate the distribution header.
+fn write_pass_through<W: io::Write>(
+  output: &mut W,
+  control_message: &Term,
+  term: &Term,
+  options: ext::WriteOptions,
+) -> Result<()> {
+  let mut packet = vec![PASS_THROUGH];
+  write_version_magic(&mut packet)?;
+  ext::write_term(&mut packet, control_message, options)?;
+  write_version_magic(&mut packet)?;
+  ext::write_outer_term(&mut packet, term, options)?;
+  write_packet(output, &packet)
+}
+
+/// Writes a control message and its payload behind a distribution header, in which the atoms of
+/// both are replaced by references to `atom_cache`. The payload is never compressed.
+///
+/// If the message is larger than the fragments of `fragmenter`, it is split into a packet with a
+/// fragment header, which holds the atom cache references of the whole message, followed by
+/// packets with the fragments that continue it. Only the first fragment is written, and the others
+/// are left in `fragmenter`. Each message that is written is followed by the next fragment that
+/// `fragmenter` holds, so that messages are interleaved with the fragments of large ones. See
+/// `write_pending_fragments` to write them all.
+fn write_with_distribution_header<W: io::Write>(
+  output: &mut W,
+  control_message: &Term,
+  term: &Term,
+  options: ext::WriteOptions,
+  atom_cache: &mut AtomCache,
+  fragmenter: Option<&mut Fragmenter>,
+) -> Result<()> {
+  let mut header = Vec::new();
+  let (atom_indices, new_entries) =
+    write_atom_cache_references(&mut header, &[control_message, term], options, atom_cache)?;
+  let mut message = Vec::new();
+  ext::write_term_with_atom_cache(&mut message, control_message, options, &atom_indices)?;
+  ext::write_term_with_atom_cache(&mut message, term, options, &atom_indices)?;
+  // The cache only changes once the message can be sent, so that it stays the same as the cache of
+  // the peer when a term cannot be encoded.
+  for (key, atom) in new_entries {
+    atom_cache.insert(key, atom);
+  }
+
+  let fragmenter = match fragmenter {
+    Some(fragmenter) => fragmenter,
+    None => return write_whole_message(output, &header, &message),
+  };
+  if message.len() <= fragmenter.max_fragment_size {
+    write_whole_message(output, &header, &message)?;
+  } else {
+    let mut packets = fragment_packets(fragmenter, &header, &message)?;
+    // The first fragment is written right away, as later messages can refer to the entries that
+    // its header adds to the atom cache of the peer.
+    if let Some(packet) = packets.pop_front() {
+      write_packet(output, &packet)?;
+    }
+    fragmenter.push(packets);
+  }
+  match fragmenter.pop() {
+    Some(packet) => write_packet(output, &packet),
+    None => Ok(()),
+  }
+}
+
+/// Writes the fragments that `fragmenter` holds until none are left.
+pub fn write_pending_fragments<W: io::Write>(
+  output: &mut W,
+  fragmenter: &mut Fragmenter,
+) -> Result<()> {
+  while let Some(packet) = fragmenter.pop() {
+    write_packet(output, &packet)?;
+  }
+  Ok(())
+}
+
+fn write_whole_message<W: io::Write>(output: &mut W, header: &[u8], message: &[u8]) -> Result<()> {
+  let mut packet = vec![ei::VERSION_MAGIC, ei::DIST_HEADER];
+  packet.extend_from_slice(header);
+  packet.extend_from_slice(message);
+  write_packet(output, &packet)
+}
+
+/// Splits a message into the packets of its fragments, the first one with the atom cache
+/// references of `header`.
+fn fragment_packets(
+  fragmenter: &mut Fragmenter,
+  header: &[u8],
+  message: &[u8],
+) -> Result<VecDeque<Vec<u8>>> {
+  let sequence_id = fragmenter.next_sequence_id;
+  fragmenter.next_sequence_id = fragmenter.next_sequence_id.wrapping_add(1);
+  let fragments = message.chunks(fragmenter.max_fragment_size);
+  let fragment_count = fragments.len() as u64;
+  (1..=fragment_count)
+    .rev()
+    .zip(fragments)
+    .map(|(fragment_id, fragment)| {
+      let mut packet = vec![ei::VERSION_MAGIC];
+      if fragment_id == fragment_count {
+        write::be_u8(&mut packet, DIST_FRAG_HEADER)?;
+        write::be_u64(&mut packet, sequence_id)?;
+        write::be_u64(&mut packet, fragment_id)?;
+        packet.extend_from_slice(header);
+      } else {
+        write::be_u8(&mut packet, DIST_FRAG_CONT)?;
+        write::be_u64(&mut packet, sequence_id)?;
+        write::be_u64(&mut packet, fragment_id)?;
+      }
+      packet.extend_from_slice(fragment);
+      Ok(packet)
+    })
+    .collect()
+}
+
+fn write_packet<W: io::Write>(output: &mut W, packet: &[u8]) -> Result<()> {
+  write::be_u32(output, ext::uncast_len(packet.len())?)?;
+  write::bytes(output, packet)?;
+  output.flush()?;
+  Ok(())
+}
+
+/// The entries that a distribution header adds to the atom cache.
+pub type NewAtomCacheEntries = Vec<(AtomCacheKey, Atom)>;
+
+/// Writes the atom cache references of a distribution header for the atoms of `terms`, with the
+/// names of the atoms that are new to `atom_cache` in the encoding of `options`. Returns the index
+/// of the reference to each atom that the terms can refer to instead of spelling it out, and the
+/// entries that the header creates, to be added to `atom_cache` once the message is sent.
+///
+/// Like in the runtime, each atom has a single slot in the cache. An atom replaces whatever atom its
+/// slot holds, unless that atom is also in the message, in which case it is spelled out. At most 255
+/// atoms are referred to by a message. The runtime picks the slot from the index of the atom in its
+/// atom table modulo 2039. There is no atom table here, so the slot is the hash of the atom name
+/// modulo 2039 instead, which only changes which atoms share a slot.
+pub fn write_atom_cache_references<W: io::Write>(
+  output: &mut W,
+  terms: &[&Term],
+  options: ext::WriteOptions,
+  atom_cache: &AtomCache,
+) -> Result<(HashMap<Atom, u8>, NewAtomCacheEntries)> {
+  let mut atom_indices = HashMap::new();
+  // The name of the atom is only written for new entries.
+  let mut references = Vec::<(AtomCacheKey, Atom, Option<Vec<u8>>)>::new();
+  let mut used_keys = HashSet::new();
+  for term in terms {
+    ext::for_each_atom(term, &mut |atom| {
+      if references.len() == MAX_ATOM_CACHE_REFERENCES || atom_indices.contains_key(atom) {
+        return Ok(());
+      }
+      let slot = hash::atom_hash(atom) % USED_ATOM_CACHE_SIZE;
+      let key = AtomCacheKey {
+        segment_index: AtomCacheReferenceFlags::from((slot >> 8) as u8).segment_index,
+        internal_index: slot as u8,
+      };
+      if !used_keys.insert(key) {
+        return Ok(());
+      }
+      let atom_bytes = if atom_cache.get(&key) == Some(atom) {
+        None
+      } else {
+        Some(match options.atom_encoding {
+          ext::AtomEncoding::Latin1 => ext::latin1_bytes(atom)?,
+          ext::AtomEncoding::Utf8 => atom.name().as_bytes().to_vec(),
+        })
+      };
+      atom_indices.insert(atom.clone(), references.len() as u8);
+      references.push((key, atom.clone(), atom_bytes));
+      Ok(())
+    })?;
+  }
+
+  write::be_u8(output, references.len() as u8)?;
+  if references.is_empty() {
+    return Ok((atom_indices, Vec::new()));
+  }
+
+  let has_long_atoms = references
+    .iter()
+    .any(|(_, _, atom_bytes)| atom_bytes.iter().any(|bytes| bytes.len() > 255));
+  let mut half_bytes = references
+    .iter()
+    .map(|(key, _, atom_bytes)| {
+      AtomCacheReferenceFlags {
+        is_new_entry: atom_bytes.is_some(),
+        segment_index: key.segment_index,
+      }
+      .into_half_byte()
+    })
+    .collect::<Vec<_>>();
+  half_bytes.push(if has_long_atoms { 0x1 } else { 0 });
+  for pair in half_bytes.chunks(2) {
+    write::be_u8(output, pair[0] | pair.get(1).map_or(0, |high| high << 4))?;
+  }
+
+  let mut new_entries = Vec::new();
+  for (key, atom, atom_bytes) in references {
+    write::be_u8(output, key.internal_index)?;
+    if let Some(atom_bytes) = atom_bytes {
+      if has_long_atoms {
+        write::be_u16(output, ext::uncast_len(atom_bytes.len())?)?;
+      } else {
+        write::be_u8(output, atom_bytes.len() as u8)?;
+      }
+      write::bytes(output, &atom_bytes)?;
+      new_entries.push((key, atom));
+    }
+  }
+  Ok((atom_indices, new_entries))
+}
+
+/// A packet that was read from the peer.
+pub enum Packet<'input> {
+  /// A message in a single packet, after its distribution header if it has one, with the atoms
+  /// that the header refers to.
+  Whole(&'input [u8], AtomCacheReferences),
+  /// A message that was reassembled from its fragments, control message included.
+  Reassembled(Vec<u8>, AtomCacheReferences),
+  /// A fragment of a message that has fragments left to receive.
+  Fragment,
+}
+
+/// Reads a packet after its version magic. Fragments are added to `fragments` until the last
+/// fragment of their message arrives. The atom cache references of fragmented messages are read
+/// from their first fragment, so that `atom_cache` is updated in the order the packets arrive.
+pub fn read_packet<'input>(
+  input: &'input [u8],
+  atom_cache: &mut AtomCache,
+  fragments: &mut Fragments,
+  options: ext::ReadOptions,
+) -> Result<Packet<'input>> {
+  let (fragment_input, tag) = read::be_u8::<u8>(input)?;
+  let message = match tag {
+    DIST_FRAG_HEADER => {
+      let (fragment_input, sequence_id) = read::be_u64(fragment_input)?;
+      let (fragment_input, fragment_count) = read::be_u64(fragment_input)?;
+      let (fragment_input, atom_references) =
+        read_atom_cache_references(fragment_input, atom_cache, options)?;
+      fragments.start(sequence_id, fragment_count, atom_references, fragment_input)?
+    }
+    DIST_FRAG_CONT => {
+      let (fragment_input, sequence_id) = read::be_u64(fragment_input)?;
+      let (fragment_input, fragment_id) = read::be_u64(fragment_input)?;
+      fragments.append(sequence_id, fragment_id, fragment_input)?
+    }
+    _ => {
+      let (input, atom_references) = read_distribution_header(input, atom_cache, options)?;
+      return Ok(Packet::Whole(input, atom_references));
+    }
+  };
+  Ok(match message {
+    Some((atom_references, bytes)) => Packet::Reassembled(bytes, atom_references),
+    None => Packet::Fragment,
+  })
+}
+
+/// Reads the distribution header of a message, if it has one, updating `atom_cache` with its new
+/// entries. Returns the atoms that the header refers to, which `ATOM_CACHE_REF` tags in the message
+/// are indices of. The new atoms are decoded according to `options.atom_encoding`.
 pub fn read_distribution_header<'input>(
   original_input: &'input [u8],
   atom_cache: &mut AtomCache,
-) -> read::IResult<'input, ()> {
+  options: ext::ReadOptions,
+) -> read::IResult<'input, AtomCacheReferences> {
   let (input, tag) = read::be_u8::<u8>(original_input)?;
   if tag != ei::DIST_HEADER {
-    return Ok((original_input, ()));
+    return Ok((original_input, AtomCacheReferences::new()));
   }
+  read_atom_cache_references(input, atom_cache, options)
+}
 
+/// Reads the atom cache references of a distribution header, after its tag, updating `atom_cache`
+/// with its new entries.
+fn read_atom_cache_references<'input>(
+  input: &'input [u8],
+  atom_cache: &mut AtomCache,
+  options: ext::ReadOptions,
+) -> read::IResult<'input, AtomCacheReferences> {
   let (input, atom_reference_count) = read::be_u8::<usize>(input)?;
   if atom_reference_count == 0 {
-    return Ok((input, ()));
+    return Ok((input, AtomCacheReferences::new()));
   }
 
   let flag_bytes_counts: usize = atom_reference_count / 2 + 1;
@@ -67,6 +439,7 @@ pub fn read_distribution_header<'input>(
     ext::AtomSizeFormat::Small
   };
 
+  let mut atoms = Vec::with_capacity(atom_reference_count);
   let input = flags.iter().try_fold(input, |input, flag| -> Result<_> {
     let (input, internal_index) = read::be_u8::<u8>(input)?;
     let key = AtomCacheKey {
@@ -77,23 +450,326 @@ pub fn read_distribution_header<'input>(
     let input = if flag.is_new_entry {
       let (input, atom_byte_len) = atom_size_format.read(input)?;
       let (input, atom_bytes) = read::take(input, atom_byte_len)?;
-      atom_cache.insert(key, Atom::new(str::from_utf8(atom_bytes)?)?);
+      let atom = ext::decode_atom(atom_bytes, options.atom_encoding)?;
+      atom_cache.insert(key, atom.clone());
+      atoms.push(atom);
       input
     } else {
+      match atom_cache.get(&key) {
+        Some(atom) => atoms.push(atom.clone()),
+        None => return Err(ErrorKind::MissingAtomCacheEntry(key).into()),
+      }
       input
     };
 
     Ok(input)
   })?;
 
-  Ok((input, ()))
+  Ok((input, AtomCacheReferences(atoms)))
 }
 
+/// Returns the flags of the reference at `index`, which are in the low half of the byte for even
+/// indices and in its high half for odd ones.
 fn get_nth_half_byte(input: &[u8], index: usize) -> u8 {
   let byte = input[index >> 1];
   if index & 0x01 == 0 {
-    byte >> 4
-  } else {
     byte & 0x0f
+  } else {
+    byte >> 4
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn atom(name: &str) -> Atom {
+    Atom::new(name).unwrap()
+  }
+
+  /// A pass through packet with its length.
+  fn pass_through(control_message: &[u8], term: &[u8]) -> Vec<u8> {
+    let len = 3 + control_message.len() + term.len();
+    let mut packet = (len as u32).to_be_bytes().to_vec();
+    packet.extend_from_slice(&[PASS_THROUGH, ei::VERSION_MAGIC]);
+    packet.extend_from_slice(control_message);
+    packet.push(ei::VERSION_MAGIC);
+    packet.extend_from_slice(term);
+    packet
+  }
+
+  #[test]
+  fn sends_are_written_as_pass_through_packets() {
+    let pid = Pid::new(Node::new(atom("a@b"), 1), 5, 0);
+    let mut pid_bytes = vec![ei::NEW_PID_EXT, ei::SMALL_ATOM_UTF8_EXT, 3];
+    pid_bytes.extend_from_slice(b"a@b");
+    pid_bytes.extend_from_slice(&[0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 1]);
+    let ok = [ei::SMALL_ATOM_UTF8_EXT, 2, b'o', b'k'];
+
+    let mut output = Vec::new();
+    write_send(
+      &mut output,
+      &pid,
+      &atom("ok").into(),
+      Default::default(),
+      None,
+      None,
+    )
+    .unwrap();
+    let mut control_message = vec![ei::SMALL_TUPLE_EXT, 3, ei::SMALL_INTEGER_EXT, 2];
+    control_message.extend_from_slice(&[ei::SMALL_ATOM_UTF8_EXT, 0]);
+    control_message.extend_from_slice(&pid_bytes);
+    assert_eq!(output, pass_through(&control_message, &ok));
+
+    let mut output = Vec::new();
+    write_registered_send(
+      &mut output,
+      &pid,
+      &atom("srv"),
+      &atom("ok").into(),
+      Default::default(),
+      None,
+      None,
+    )
+    .unwrap();
+    let mut control_message = vec![ei::SMALL_TUPLE_EXT, 4, ei::SMALL_INTEGER_EXT, 6];
+    control_message.extend_from_slice(&pid_bytes);
+    control_message.extend_from_slice(&[ei::SMALL_ATOM_UTF8_EXT, 0]);
+    control_message.extend_from_slice(&[ei::SMALL_ATOM_UTF8_EXT, 3, b's', b'r', b'v']);
+    assert_eq!(output, pass_through(&control_message, &ok));
+  }
+
+  #[test]
+  fn header_atoms_are_cached() {
+    let cached = AtomCacheKey {
+      segment_index: AtomCacheSegment::S1,
+      internal_index: 7,
+    };
+    let new = AtomCacheKey {
+      segment_index: AtomCacheSegment::S2,
+      internal_index: 5,
+    };
+    let mut atom_cache = AtomCache::new();
+    atom_cache.insert(cached, atom("error"));
+
+    // A new entry in segment 2 and a cached one in segment 1, with small atoms.
+    let header = [
+      ei::DIST_HEADER,
+      2,
+      0x1a,
+      0x00,
+      5,
+      2,
+      b'o',
+      b'k',
+      7,
+      ei::VERSION_MAGIC,
+    ];
+    let (input, references) =
+      read_distribution_header(&header, &mut atom_cache, Default::default()).unwrap();
+    assert_eq!(input, [ei::VERSION_MAGIC]);
+    assert_eq!(references.0, [atom("ok"), atom("error")]);
+    assert_eq!(atom_cache.get(&new), Some(&atom("ok")));
+
+    // The second reference is no longer a new entry, and the cache has nothing at its key.
+    let header = [ei::DIST_HEADER, 2, 0x1a, 0x00, 5, 2, b'o', b'k', 8];
+    match read_distribution_header(&header, &mut atom_cache, Default::default()) {
+      Err(Error(ErrorKind::MissingAtomCacheEntry(key), _)) => assert_eq!(
+        key,
+        AtomCacheKey {
+          segment_index: AtomCacheSegment::S1,
+          internal_index: 8,
+        }
+      ),
+      result => panic!("{:?}", result),
+    }
+  }
+
+  #[test]
+  fn sent_atoms_are_cached() {
+    let to = Pid::new(Node::new(atom("a@b"), 1), 5, 0);
+    let term = Term::from(Tuple(
+      vec![atom("ok").into(), atom("ok").into(), atom("done").into()].into_boxed_slice(),
+    ));
+    let mut atom_cache = AtomCache::new();
+    let mut peer_atom_cache = AtomCache::new();
+    for _ in 0..2 {
+      let mut output = Vec::new();
+      write_send(
+        &mut output,
+        &to,
+        &term,
+        Default::default(),
+        Some(&mut atom_cache),
+        None,
+      )
+      .unwrap();
+      assert_eq!(
+        output.len() - 4,
+        u32::from_be_bytes([output[0], output[1], output[2], output[3]]) as usize
+      );
+
+      let (input, ()) = read_version_magic(&output[4..]).unwrap();
+      let (input, references) =
+        read_distribution_header(input, &mut peer_atom_cache, Default::default()).unwrap();
+      // The empty atom, the node name, `ok` and `done`, each referred to once.
+      assert_eq!(references.0.len(), 4);
+      let (input, _) = ext::read_term(input, &references).unwrap();
+      let (input, read) = ext::read_term(input, &references).unwrap();
+      assert!(input.is_empty());
+      assert_eq!(read, term);
+    }
+    // The second message only referred to the entries that the first one created.
+    assert_eq!(atom_cache.entries, peer_atom_cache.entries);
+    assert_eq!(atom_cache.entries.len(), 4);
+  }
+
+  /// Reads the payloads of the messages in the packets of `output`, in the order their last packet
+  /// arrives, and counts the packets.
+  fn read_payloads(mut output: &[u8]) -> (Vec<Term>, usize) {
+    let mut atom_cache = AtomCache::new();
+    let mut fragments = Fragments::new(1024);
+    let mut payloads = Vec::new();
+    let mut packet_count = 0;
+    while !output.is_empty() {
+      let len = u32::from_be_bytes([output[0], output[1], output[2], output[3]]) as usize;
+      let (packet, rest) = output[4..].split_at(len);
+      output = rest;
+      packet_count += 1;
+      let (packet, ()) = read_version_magic(packet).unwrap();
+      let (message, references) =
+        match read_packet(packet, &mut atom_cache, &mut fragments, Default::default()) {
+          Ok(Packet::Whole(message, references)) => (message.to_vec(), references),
+          Ok(Packet::Reassembled(message, references)) => (message, references),
+          Ok(Packet::Fragment) => continue,
+          Err(error) => panic!("{}", error),
+        };
+      let (rest, _) = ext::read_term(&message, &references).unwrap();
+      let (rest, payload) = ext::read_term(rest, &references).unwrap();
+      assert!(rest.is_empty());
+      payloads.push(payload);
+    }
+    (payloads, packet_count)
+  }
+
+  #[test]
+  fn large_messages_are_fragmented() {
+    let to = Pid::new(Node::new(atom("a@b"), 1), 5, 0);
+    let term = Term::from(Binary(vec![7; 100].into_boxed_slice()));
+    let mut atom_cache = AtomCache::new();
+    let mut fragmenter = Fragmenter::new(40);
+    let mut output = Vec::new();
+    write_send(
+      &mut output,
+      &to,
+      &term,
+      Default::default(),
+      Some(&mut atom_cache),
+      Some(&mut fragmenter),
+    )
+    .unwrap();
+    // The first fragment and the next one that is pending.
+    assert_eq!(read_payloads(&output), (vec![], 2));
+    assert!(fragmenter.has_pending_fragments());
+
+    write_pending_fragments(&mut output, &mut fragmenter).unwrap();
+    assert!(!fragmenter.has_pending_fragments());
+    assert_eq!(read_payloads(&output), (vec![term], 4));
+  }
+
+  #[test]
+  fn messages_are_sent_between_fragments() {
+    let to = Pid::new(Node::new(atom("a@b"), 1), 5, 0);
+    let large = Term::from(Binary(vec![7; 100].into_boxed_slice()));
+    let small = Term::from(atom("small"));
+    let mut atom_cache = AtomCache::new();
+    let mut fragmenter = Fragmenter::new(40);
+    let mut output = Vec::new();
+    for term in &[&large, &small] {
+      write_send(
+        &mut output,
+        &to,
+        term,
+        Default::default(),
+        Some(&mut atom_cache),
+        Some(&mut fragmenter),
+      )
+      .unwrap();
+    }
+    write_pending_fragments(&mut output, &mut fragmenter).unwrap();
+
+    // The small message arrives whole while the fragments of the large one are still being sent.
+    assert_eq!(read_payloads(&output), (vec![small, large], 5));
+  }
+
+  #[test]
+  fn atom_cache_is_unchanged_when_a_message_cannot_be_written() {
+    let to = Pid::new(Node::new(atom("peer@host"), 4), 1, 0);
+    let options = ext::WriteOptions {
+      creation_format: ext::CreationFormat::Old,
+      ..Default::default()
+    };
+    let mut atom_cache = AtomCache::new();
+    let mut output = Vec::new();
+    let result = write_send(
+      &mut output,
+      &to,
+      &atom("hello").into(),
+      options,
+      Some(&mut atom_cache),
+      None,
+    );
+    assert!(result.is_err());
+    assert!(atom_cache.entries.is_empty());
+    assert!(output.is_empty());
+  }
+
+  #[test]
+  fn header_atoms_are_written_in_the_atom_encoding() {
+    let to = Pid::new(Node::new(atom("peer@host"), 1), 1, 0);
+    let options = ext::WriteOptions {
+      atom_encoding: ext::AtomEncoding::Latin1,
+      ..Default::default()
+    };
+    let mut atom_cache = AtomCache::new();
+    let mut output = Vec::new();
+    write_send(
+      &mut output,
+      &to,
+      &atom("café").into(),
+      options,
+      Some(&mut atom_cache),
+      None,
+    )
+    .unwrap();
+    let packet = &output[4..];
+    assert!(packet.windows(4).any(|bytes| bytes == b"caf\xe9"));
+    assert!(!packet.windows(5).any(|bytes| bytes == "café".as_bytes()));
+
+    // The header atoms are read back in the same encoding.
+    let (header, ()) = read_version_magic(packet).unwrap();
+    let read_options = ext::ReadOptions {
+      atom_encoding: ext::AtomEncoding::Latin1,
+      ..Default::default()
+    };
+    let mut peer_atom_cache = AtomCache::new();
+    let (input, references) =
+      read_distribution_header(header, &mut peer_atom_cache, read_options).unwrap();
+    assert!(references.0.contains(&atom("café")));
+    let (input, _) = ext::read_term(input, &references).unwrap();
+    let (_, payload) = ext::read_term(input, &references).unwrap();
+    assert_eq!(payload, atom("café").into());
+    assert!(read_distribution_header(header, &mut AtomCache::new(), Default::default()).is_err());
+
+    let result = write_send(
+      &mut output,
+      &to,
+      &atom("ā").into(),
+      options,
+      Some(&mut atom_cache),
+      None,
+    );
+    assert!(result.is_err());
+    assert!(atom_cache.entries.values().all(|atom| atom.name() != "ā"));
   }
 }
@@ -71,5 +71,5 @@ pub fn be_u64(input: &[u8]) -> IResult<u64> {
 
 pub fn be_f64(input: &[u8]) -> IResult<f64> {
   let (input, value) = be_u64(input)?;
-  Ok((input, value as f64))
+  Ok((input, f64::from_bits(value)))
 }
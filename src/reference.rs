@@ -0,0 +1,25 @@
+use crate::ty::*;
+use std::{fmt, result};
+
+impl Reference {
+  /// Writes the reference like the runtime, with the number of its node and then its most
+  /// significant id first, as in `#Ref<5.3.2.1>`.
+  pub(crate) fn write_numbered(
+    &self,
+    formatter: &mut fmt::Formatter,
+    node_number: usize,
+  ) -> result::Result<(), fmt::Error> {
+    write!(formatter, "#Ref<{}", node_number)?;
+    for id in self.ids.iter().rev() {
+      write!(formatter, ".{}", id)?;
+    }
+    formatter.write_str(">")
+  }
+}
+
+/// Formats the reference like a local reference, as in `#Ref<0.3.2.1>`. See `Term::display_with`.
+impl fmt::Display for Reference {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    self.write_numbered(formatter, 0)
+  }
+}
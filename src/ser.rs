@@ -0,0 +1,512 @@
+//! Serde data format for the external term format, on the serialization side.
+//!
+//! Values are first serialized to a `Term`, which `to_bytes` then encodes. The mapping follows what
+//! Erlang code usually expects:
+//!
+//! * booleans are the atoms `true` and `false`;
+//! * integers and floats are numbers, with integers too large for 32 bits as big integers;
+//! * strings are UTF-8 binaries, and byte arrays are binaries when they are marked with
+//!   `serde_bytes`. Serde sees a plain `Vec<u8>` or `&[u8]` as a sequence, so without it they are
+//!   lists of integers;
+//! * `None` is the atom `undefined` and `Some(value)` is `value`;
+//! * sequences are proper lists and tuples are tuples;
+//! * maps are maps;
+//! * structs and enum variants follow `SerdeOptions`.
+//!
+//! Record tags and variants are atoms spelled exactly like the Rust name, so `Person` is tagged
+//! `'Person'`. This differs from the `FromTerm` and `IntoTerm` derive, which names records in snake
+//! case; `#[serde(rename = "person")]` makes them agree.
+
+use crate::{err::*, ext, protocol, ty::*};
+use serde::ser;
+use std::fmt;
+
+/// How structs are represented.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StructFormat {
+  /// A map from field names, as atoms, to values: `#{name => <<"joe">>, age => 42}`.
+  Map,
+  /// A record-style tuple tagged with the struct name: `{'Person', <<"joe">>, 42}`.
+  Record,
+}
+
+/// How enum variants that carry data are represented. Unit variants are always atoms.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EnumFormat {
+  /// A tuple tagged with the variant name: `{circle, 1.0}`. The fields of a struct variant follow
+  /// the tag as a record would, or as a single map, depending on `StructFormat`.
+  TaggedTuple,
+  /// A map with a single entry from the variant name to its data: `#{circle => 1.0}`.
+  Map,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SerdeOptions {
+  pub struct_format: StructFormat,
+  pub enum_format: EnumFormat,
+}
+
+impl Default for SerdeOptions {
+  fn default() -> Self {
+    SerdeOptions {
+      struct_format: StructFormat::Map,
+      enum_format: EnumFormat::TaggedTuple,
+    }
+  }
+}
+
+pub(crate) const NONE_ATOM: &str = "undefined";
+
+impl ser::Error for Error {
+  fn custom<T: fmt::Display>(message: T) -> Self {
+    ErrorKind::Serde(message.to_string()).into()
+  }
+}
+
+pub fn to_term<T>(value: &T) -> Result<Term>
+where
+  T: ser::Serialize + ?Sized,
+{
+  to_term_with_options(value, Default::default())
+}
+
+pub fn to_term_with_options<T>(value: &T, options: SerdeOptions) -> Result<Term>
+where
+  T: ser::Serialize + ?Sized,
+{
+  value.serialize(Serializer::new(options))
+}
+
+/// Serializes `value` to the external term format, starting with the version byte.
+pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>>
+where
+  T: ser::Serialize + ?Sized,
+{
+  to_bytes_with_options(value, Default::default())
+}
+
+pub fn to_bytes_with_options<T>(value: &T, options: SerdeOptions) -> Result<Vec<u8>>
+where
+  T: ser::Serialize + ?Sized,
+{
+  let term = to_term_with_options(value, options)?;
+  let mut output = Vec::new();
+  protocol::write_version_magic(&mut output)?;
+  ext::write_outer_term(&mut output, &term, Default::default())?;
+  Ok(output)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Serializer {
+  options: SerdeOptions,
+}
+
+impl Serializer {
+  pub fn new(options: SerdeOptions) -> Self {
+    Serializer { options }
+  }
+
+  fn wrap_variant(self, variant: &'static str, data: Term) -> Result<Term> {
+    let tag = atom(variant)?;
+    match self.options.enum_format {
+      EnumFormat::TaggedTuple => Ok(Tuple(vec![tag, data].into_boxed_slice()).into()),
+      EnumFormat::Map => Ok(Map(vec![(tag, data)].into_boxed_slice()).into()),
+    }
+  }
+}
+
+fn atom(name: &str) -> Result<Term> {
+  Ok(Atom::new(name)?.into())
+}
+
+impl ser::Serializer for Serializer {
+  type Ok = Term;
+  type Error = Error;
+
+  type SerializeSeq = SerializeList;
+  type SerializeTuple = SerializeTuple;
+  type SerializeTupleStruct = SerializeTuple;
+  type SerializeTupleVariant = SerializeTupleVariant;
+  type SerializeMap = SerializeMap;
+  type SerializeStruct = SerializeStruct;
+  type SerializeStructVariant = SerializeStructVariant;
+
+  fn serialize_bool(self, value: bool) -> Result<Term> {
+    atom(if value { "true" } else { "false" })
+  }
+
+  fn serialize_i8(self, value: i8) -> Result<Term> {
+    Ok(Term::Integer(value.into()))
+  }
+
+  fn serialize_i16(self, value: i16) -> Result<Term> {
+    Ok(Term::Integer(value.into()))
+  }
+
+  fn serialize_i32(self, value: i32) -> Result<Term> {
+    Ok(Term::Integer(value))
+  }
+
+  fn serialize_i64(self, value: i64) -> Result<Term> {
+    Ok(value.into())
+  }
+
+  fn serialize_u8(self, value: u8) -> Result<Term> {
+    Ok(Term::Integer(value.into()))
+  }
+
+  fn serialize_u16(self, value: u16) -> Result<Term> {
+    Ok(Term::Integer(value.into()))
+  }
+
+  fn serialize_u32(self, value: u32) -> Result<Term> {
+    Ok(u64::from(value).into())
+  }
+
+  fn serialize_u64(self, value: u64) -> Result<Term> {
+    Ok(value.into())
+  }
+
+  fn serialize_f32(self, value: f32) -> Result<Term> {
+    Ok(Term::Float(value.into()))
+  }
+
+  fn serialize_f64(self, value: f64) -> Result<Term> {
+    Ok(Term::Float(value))
+  }
+
+  fn serialize_char(self, value: char) -> Result<Term> {
+    Ok(u64::from(u32::from(value)).into())
+  }
+
+  fn serialize_str(self, value: &str) -> Result<Term> {
+    self.serialize_bytes(value.as_bytes())
+  }
+
+  fn serialize_bytes(self, value: &[u8]) -> Result<Term> {
+    Ok(Binary(value.to_vec().into_boxed_slice()).into())
+  }
+
+  fn serialize_none(self) -> Result<Term> {
+    atom(NONE_ATOM)
+  }
+
+  fn serialize_some<T>(self, value: &T) -> Result<Term>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<Term> {
+    Ok(Tuple(Vec::new().into_boxed_slice()).into())
+  }
+
+  fn serialize_unit_struct(self, name: &'static str) -> Result<Term> {
+    atom(name)
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+  ) -> Result<Term> {
+    atom(variant)
+  }
+
+  fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Term>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T>(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    value: &T,
+  ) -> Result<Term>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    let data = value.serialize(self)?;
+    self.wrap_variant(variant, data)
+  }
+
+  fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList> {
+    Ok(SerializeList {
+      serializer: self,
+      elements: Vec::with_capacity(len.unwrap_or(0)),
+    })
+  }
+
+  fn serialize_tuple(self, len: usize) -> Result<SerializeTuple> {
+    Ok(SerializeTuple {
+      serializer: self,
+      elements: Vec::with_capacity(len),
+    })
+  }
+
+  fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeTuple> {
+    self.serialize_tuple(len)
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    len: usize,
+  ) -> Result<SerializeTupleVariant> {
+    Ok(SerializeTupleVariant {
+      serializer: self,
+      variant,
+      elements: Vec::with_capacity(len + 1),
+    })
+  }
+
+  fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap> {
+    Ok(SerializeMap {
+      serializer: self,
+      entries: Vec::with_capacity(len.unwrap_or(0)),
+      key: None,
+    })
+  }
+
+  fn serialize_struct(self, name: &'static str, len: usize) -> Result<SerializeStruct> {
+    Ok(SerializeStruct::new(self, name, len))
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    len: usize,
+  ) -> Result<SerializeStructVariant> {
+    Ok(SerializeStructVariant {
+      serializer: self,
+      variant,
+      fields: SerializeStruct::new(self, variant, len),
+    })
+  }
+}
+
+pub struct SerializeList {
+  serializer: Serializer,
+  elements: Vec<Term>,
+}
+
+impl ser::SerializeSeq for SerializeList {
+  type Ok = Term;
+  type Error = Error;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    self.elements.push(value.serialize(self.serializer)?);
+    Ok(())
+  }
+
+  fn end(self) -> Result<Term> {
+    Ok(List::new(self.elements).into())
+  }
+}
+
+pub struct SerializeTuple {
+  serializer: Serializer,
+  elements: Vec<Term>,
+}
+
+impl ser::SerializeTuple for SerializeTuple {
+  type Ok = Term;
+  type Error = Error;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    self.elements.push(value.serialize(self.serializer)?);
+    Ok(())
+  }
+
+  fn end(self) -> Result<Term> {
+    Ok(Tuple(self.elements.into_boxed_slice()).into())
+  }
+}
+
+impl ser::SerializeTupleStruct for SerializeTuple {
+  type Ok = Term;
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    ser::SerializeTuple::serialize_element(self, value)
+  }
+
+  fn end(self) -> Result<Term> {
+    ser::SerializeTuple::end(self)
+  }
+}
+
+pub struct SerializeTupleVariant {
+  serializer: Serializer,
+  variant: &'static str,
+  elements: Vec<Term>,
+}
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+  type Ok = Term;
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    self.elements.push(value.serialize(self.serializer)?);
+    Ok(())
+  }
+
+  fn end(mut self) -> Result<Term> {
+    match self.serializer.options.enum_format {
+      EnumFormat::TaggedTuple => {
+        self.elements.insert(0, atom(self.variant)?);
+        Ok(Tuple(self.elements.into_boxed_slice()).into())
+      }
+      EnumFormat::Map => {
+        let data = Tuple(self.elements.into_boxed_slice()).into();
+        self.serializer.wrap_variant(self.variant, data)
+      }
+    }
+  }
+}
+
+pub struct SerializeMap {
+  serializer: Serializer,
+  entries: Vec<(Term, Term)>,
+  key: Option<Term>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+  type Ok = Term;
+  type Error = Error;
+
+  fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    self.key = Some(key.serialize(self.serializer)?);
+    Ok(())
+  }
+
+  fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    let key = self
+      .key
+      .take()
+      .expect("serialize_value called before serialize_key");
+    self.entries.push((key, value.serialize(self.serializer)?));
+    Ok(())
+  }
+
+  fn end(self) -> Result<Term> {
+    Ok(Map(self.entries.into_boxed_slice()).into())
+  }
+}
+
+pub struct SerializeStruct {
+  serializer: Serializer,
+  name: &'static str,
+  fields: Vec<(Term, Term)>,
+}
+
+impl SerializeStruct {
+  fn new(serializer: Serializer, name: &'static str, len: usize) -> Self {
+    SerializeStruct {
+      serializer,
+      name,
+      fields: Vec::with_capacity(len),
+    }
+  }
+
+  /// Returns the fields as a record, without the tag, or as a map.
+  fn into_untagged_term(self) -> Term {
+    match self.serializer.options.struct_format {
+      StructFormat::Map => Map(self.fields.into_boxed_slice()).into(),
+      StructFormat::Record => {
+        let values = self
+          .fields
+          .into_iter()
+          .map(|(_, value)| value)
+          .collect::<Vec<_>>();
+        Tuple(values.into_boxed_slice()).into()
+      }
+    }
+  }
+}
+
+impl ser::SerializeStruct for SerializeStruct {
+  type Ok = Term;
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    self
+      .fields
+      .push((atom(key)?, value.serialize(self.serializer)?));
+    Ok(())
+  }
+
+  fn end(self) -> Result<Term> {
+    match self.serializer.options.struct_format {
+      StructFormat::Map => Ok(self.into_untagged_term()),
+      StructFormat::Record => {
+        let mut elements = Vec::with_capacity(self.fields.len() + 1);
+        elements.push(atom(self.name)?);
+        elements.extend(self.fields.into_iter().map(|(_, value)| value));
+        Ok(Tuple(elements.into_boxed_slice()).into())
+      }
+    }
+  }
+}
+
+pub struct SerializeStructVariant {
+  serializer: Serializer,
+  variant: &'static str,
+  fields: SerializeStruct,
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+  type Ok = Term;
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+  where
+    T: ser::Serialize + ?Sized,
+  {
+    ser::SerializeStruct::serialize_field(&mut self.fields, key, value)
+  }
+
+  fn end(self) -> Result<Term> {
+    match (
+      self.serializer.options.enum_format,
+      self.serializer.options.struct_format,
+    ) {
+      (EnumFormat::TaggedTuple, StructFormat::Record) => ser::SerializeStruct::end(self.fields),
+      _ => {
+        let data = self.fields.into_untagged_term();
+        self.serializer.wrap_variant(self.variant, data)
+      }
+    }
+  }
+}
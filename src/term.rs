@@ -1,4 +1,5 @@
 use crate::ty::*;
+use std::{fmt, result};
 
 impl From<Atom> for Term {
   fn from(atom: Atom) -> Self {
@@ -6,12 +7,40 @@ impl From<Atom> for Term {
   }
 }
 
+/// Converts a `BigInteger` to a term, using `Term::Integer` if it is small enough.
+impl From<BigInteger> for Term {
+  fn from(big_integer: BigInteger) -> Self {
+    match num_traits::ToPrimitive::to_i32(&big_integer) {
+      Some(value) => Term::Integer(value),
+      None => Term::BigInteger(big_integer),
+    }
+  }
+}
+
+impl From<i64> for Term {
+  fn from(value: i64) -> Self {
+    BigInteger::from(value).into()
+  }
+}
+
+impl From<u64> for Term {
+  fn from(value: u64) -> Self {
+    BigInteger::from(value).into()
+  }
+}
+
 impl From<Pid> for Term {
   fn from(pid: Pid) -> Self {
     Term::Pid(pid)
   }
 }
 
+impl From<Port> for Term {
+  fn from(port: Port) -> Self {
+    Term::Port(port)
+  }
+}
+
 impl From<Reference> for Term {
   fn from(reference: Reference) -> Self {
     Term::Reference(reference)
@@ -24,9 +53,14 @@ impl From<Tuple> for Term {
   }
 }
 
+/// Converts a list with no elements to its tail, so that `List::new(vec![])` becomes `[]`.
 impl From<List> for Term {
   fn from(list: List) -> Self {
-    Term::List(list)
+    if list.elements.is_empty() {
+      *list.tail
+    } else {
+      Term::List(list)
+    }
   }
 }
 
@@ -36,18 +70,67 @@ impl From<Binary> for Term {
   }
 }
 
+impl From<BitBinary> for Term {
+  fn from(bit_binary: BitBinary) -> Self {
+    Term::BitBinary(bit_binary)
+  }
+}
+
+impl From<Map> for Term {
+  fn from(map: Map) -> Self {
+    Term::Map(map)
+  }
+}
+
+impl From<Export> for Term {
+  fn from(export: Export) -> Self {
+    Term::Export(export)
+  }
+}
+
+impl From<Fun> for Term {
+  fn from(fun: Fun) -> Self {
+    Term::Fun(fun)
+  }
+}
+
 impl Term {
   pub fn kind(&self) -> TermKind {
     match self {
       Term::Nil => TermKind::Nil,
       Term::Integer(_) => TermKind::Integer,
+      Term::BigInteger(_) => TermKind::Integer,
       Term::Float(_) => TermKind::Float,
       Term::Atom(_) => TermKind::Atom,
       Term::Pid { .. } => TermKind::Pid,
+      Term::Port { .. } => TermKind::Port,
       Term::Reference { .. } => TermKind::Reference,
       Term::Tuple(_) => TermKind::Tuple,
       Term::List(_) => TermKind::List,
       Term::Binary(_) => TermKind::Binary,
+      Term::BitBinary(_) => TermKind::BitBinary,
+      Term::Map(_) => TermKind::Map,
+      Term::Export(_) | Term::Fun(_) => TermKind::Fun,
     }
   }
 }
+
+impl fmt::Display for TermKind {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+    formatter.write_str(match self {
+      TermKind::Nil => "empty list",
+      TermKind::Integer => "integer",
+      TermKind::Float => "float",
+      TermKind::Atom => "atom",
+      TermKind::Pid => "pid",
+      TermKind::Port => "port",
+      TermKind::Reference => "reference",
+      TermKind::Tuple => "tuple",
+      TermKind::List => "list",
+      TermKind::Binary => "binary",
+      TermKind::BitBinary => "bitstring",
+      TermKind::Map => "map",
+      TermKind::Fun => "fun",
+    })
+  }
+}
@@ -1,23 +1,133 @@
-use crate::{err::*, ty::*};
+use crate::{order::canonical, ty::*};
 
 impl<'term> TermViewBuffer<'term> {
   pub fn new() -> Self {
     TermViewBuffer {
       atoms: Default::default(),
+      views: Default::default(),
     }
   }
 
-  pub fn view(&self, term: &'term Term) -> TermView<'term> {
-    match term {
+  /// Views `term`, storing the views of nested terms in this buffer so that tuples and lists can
+  /// be matched with slice patterns.
+  ///
+  /// Terms are viewed the way Erlang sees them, so that a pattern matches however they were built
+  /// or encoded: a list without elements is viewed as its tail, the elements of lists in the tail of
+  /// a list are among its elements, and big integers that fit in an `i32` are integers.
+  pub fn view(&'term self, term: &'term Term) -> TermView<'term> {
+    match canonical(term) {
       Term::Nil => TermView::Nil,
       Term::Integer(value) => TermView::Integer(*value),
+      Term::BigInteger(value) => match num_traits::ToPrimitive::to_i32(value) {
+        Some(value) => TermView::Integer(value),
+        None => TermView::BigInteger(value),
+      },
       Term::Float(value) => TermView::Float(*value),
       Term::Atom(atom) => TermView::Atom(atom.name()),
-      Term::Pid(_) => unimplemented!(),
-      Term::Reference(_) => unimplemented!(),
-      Term::Tuple(_) => unimplemented!(),
-      Term::List(_) => unimplemented!(),
-      Term::Binary(_) => unimplemented!(),
+      Term::Pid(pid) => TermView::Pid(pid),
+      Term::Port(port) => TermView::Port(port),
+      Term::Reference(reference) => TermView::Reference(reference),
+      Term::Tuple(Tuple(elements)) => TermView::Tuple(self.view_all(elements.iter())),
+      Term::List(list) => {
+        let views = self.view_all(list.iter().chain(Some(list.tail())));
+        let (tail, elements) = views.split_last().unwrap();
+        TermView::List(elements, tail)
+      }
+      Term::Binary(Binary(bytes)) => TermView::Binary(bytes),
+      Term::BitBinary(bit_binary) => TermView::BitBinary(bit_binary),
+      Term::Map(map) => TermView::Map(map),
+      Term::Export(export) => TermView::Export(export),
+      Term::Fun(fun) => TermView::Fun(fun),
+    }
+  }
+
+  fn view_all<I>(&'term self, terms: I) -> &'term [TermView<'term>]
+  where
+    I: Iterator<Item = &'term Term>,
+  {
+    let views = terms.map(|term| self.view(term)).collect::<Vec<_>>();
+    self.views.push(views.into_boxed_slice())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn terms_are_viewed_recursively() {
+    let ok = Term::Atom(Atom::new("ok").unwrap());
+    let term = Tuple(
+      vec![
+        ok,
+        List::improper(vec![Term::Integer(1), Term::Float(2.0)], Term::Integer(3)).into(),
+        Binary(Box::new(*b"hi")).into(),
+      ]
+      .into_boxed_slice(),
+    )
+    .into();
+
+    let buffer = TermViewBuffer::new();
+    match buffer.view(&term) {
+      TermView::Tuple(
+        &[TermView::Atom("ok"), TermView::List(elements, tail), TermView::Binary(b"hi")],
+      ) => {
+        match elements {
+          [TermView::Integer(1), TermView::Float(value)] => assert_eq!(*value, 2.0),
+          elements => panic!("{:?}", elements),
+        }
+        match tail {
+          TermView::Integer(3) => (),
+          tail => panic!("{:?}", tail),
+        }
+      }
+      view => panic!("{:?}", view),
+    }
+
+    match buffer.view(&List::new(vec![Term::Nil]).into()) {
+      TermView::List([TermView::Nil], TermView::Nil) => (),
+      view => panic!("{:?}", view),
+    }
+  }
+
+  #[test]
+  fn terms_are_viewed_like_erlang_sees_them() {
+    let list = |elements: Vec<Term>, tail: Term| {
+      Term::List(List {
+        elements: elements.into_boxed_slice(),
+        tail: Box::new(tail),
+      })
+    };
+    let ok = || Term::Atom(Atom::new("ok").unwrap());
+    let empty = list(vec![], Term::Nil);
+    let tail_only = list(vec![], list(vec![], ok()));
+    let nested = list(
+      vec![Term::Integer(1)],
+      list(vec![], list(vec![Term::Integer(2)], ok())),
+    );
+    let small = Term::BigInteger(BigInteger::from(-5i64));
+    let big = Term::BigInteger(BigInteger::from(1i64 << 31));
+
+    let buffer = TermViewBuffer::new();
+    match buffer.view(&empty) {
+      TermView::Nil => (),
+      view => panic!("{:?}", view),
+    }
+    match buffer.view(&tail_only) {
+      TermView::Atom("ok") => (),
+      view => panic!("{:?}", view),
+    }
+    match buffer.view(&nested) {
+      TermView::List([TermView::Integer(1), TermView::Integer(2)], TermView::Atom("ok")) => (),
+      view => panic!("{:?}", view),
+    }
+    match buffer.view(&small) {
+      TermView::Integer(-5) => (),
+      view => panic!("{:?}", view),
+    }
+    match buffer.view(&big) {
+      TermView::BigInteger(value) => assert_eq!(value.to_string(), "2147483648"),
+      view => panic!("{:?}", view),
     }
   }
 }
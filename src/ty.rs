@@ -1,4 +1,4 @@
-use std::{collections, str};
+use std::{cell, collections, slice, str};
 
 #[derive(Copy, Clone, Debug)]
 pub enum NameKind {
@@ -8,76 +8,186 @@ pub enum NameKind {
 }
 
 /// An atom is a constant term with a name made of up to 255 unicode code points.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct Atom(pub(crate) Box<str>);
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct Node {
   pub name: Atom,
-  pub(crate) serial_number: u8,
+  pub(crate) serial_number: u32,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Reference {
   pub node: Node,
-  pub id: u32,
+  /// The id words, least significant first, as they are encoded. Legacy references have a single
+  /// word, newer ones up to five.
+  pub ids: Box<[u32]>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Pid {
   pub node: Node,
-  pub(crate) id: u16,
-  pub(crate) serial: u16,
+  pub(crate) id: u32,
+  pub(crate) serial: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Port {
+  pub node: Node,
+  pub(crate) id: u64,
+}
+
+/// An integer that does not fit in a machine word, stored as a sign and a magnitude.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BigInteger {
+  pub(crate) is_negative: bool,
+  pub(crate) digits: Box<[u8]>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Tuple(pub Box<[Term]>);
 
-#[derive(Debug)]
-pub struct List(pub Box<[Term]>);
+/// A list made of its elements followed by its tail. The tail of a proper list is `Term::Nil`.
+///
+/// The tail can itself be a list, whose elements then belong to this list: `[1 | [2]]` is the
+/// proper list `[1, 2]`. `List::improper` moves them into the elements.
+#[derive(Debug, Clone)]
+pub struct List {
+  pub elements: Box<[Term]>,
+  pub tail: Box<Term>,
+}
 
-#[derive(Debug)]
+/// Iterates over the elements of a list, then over those of the lists in its tail. See
+/// `List::iter`.
+#[derive(Debug, Clone)]
+pub struct ListIter<'list> {
+  pub(crate) elements: slice::Iter<'list, Term>,
+  pub(crate) tail: &'list Term,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct Binary(pub Box<[u8]>);
 
-#[derive(Debug)]
+/// A bitstring whose size is not a whole number of bytes, such as `<<1,2:3>>`.
+///
+/// Bitstrings are ordered like Erlang orders them, bit by bit, as the unused bits of their last
+/// byte are always zero.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct BitBinary {
+  pub(crate) bytes: Box<[u8]>,
+  pub(crate) trailing_bits: u8,
+}
+
+/// An external function, as in `fun lists:map/2`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Export {
+  pub module: Atom,
+  pub function: Atom,
+  pub arity: u8,
+}
+
+/// A closure, as encoded by the node that created it. It cannot be called here, but it can be sent
+/// back to a node that has its module loaded.
+#[derive(Debug, Clone)]
+pub struct Fun {
+  pub module: Atom,
+  pub arity: u8,
+  /// The MD5 of the code of the module.
+  pub uniq: [u8; 16],
+  /// The index of the fun in the fun table of the module.
+  pub index: u32,
+  /// The index of the fun in the module, from before funs had a global index.
+  pub old_index: u32,
+  /// The hash of the parse tree of the fun, from before the MD5 of the module was used.
+  pub old_uniq: u32,
+  /// The process that created the fun.
+  pub pid: Pid,
+  /// The values of the variables the fun captured, in the order they are encoded.
+  pub free_vars: Box<[Term]>,
+  /// The `NEW_FUN_EXT` the fun was read from, without its tag, or `None` for funs built here. As
+  /// long as the other fields still decode from it, the fun is written back as these bytes rather
+  /// than encoded with the `WriteOptions`, so that it is sent back byte for byte as it was received.
+  pub encoded: Option<Box<[u8]>>,
+}
+
+/// A map, as its key/value pairs in the order they were decoded.
+#[derive(Debug, Clone)]
+pub struct Map(pub Box<[(Term, Term)]>);
+
+/// Terms are equal, ordered and hashed like Erlang's exact comparisons, such as `=:=`, so `1` and
+/// `1.0` are different terms. `Term::compare` coerces numbers like `==` does.
+#[derive(Debug, Clone)]
 pub enum Term {
   Nil,
   Integer(i32),
+  BigInteger(BigInteger),
   Float(f64),
   Atom(Atom),
   Pid(Pid),
+  Port(Port),
   Reference(Reference),
   Tuple(Tuple),
   List(List),
   Binary(Binary),
+  BitBinary(BitBinary),
+  Map(Map),
+  Export(Export),
+  Fun(Fun),
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum TermKind {
   Nil,
   Integer,
   Float,
   Atom,
   Pid,
+  Port,
   Reference,
   Tuple,
   List,
   Binary,
+  BitBinary,
+  Map,
+  Fun,
+}
+
+/// Where, inside the term being converted, a conversion failed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ConversionPathSegment {
+  /// The element at the given 1-based position, as in `element/2`.
+  TupleElement(usize),
+  /// The element at the given 1-based position, as in `lists:nth/2`.
+  ListElement(usize),
+  /// The key of the entry at the given 0-based position, in decoding order.
+  MapKey(usize),
+  /// The value of the entry at the given 0-based position, in decoding order.
+  MapValue(usize),
+  RecordField {
+    record: &'static str,
+    field: &'static str,
+  },
 }
 
+/// The path from the root of a term to where a conversion failed, outermost segment first.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ConversionPath(pub Vec<ConversionPathSegment>);
+
 #[derive(Debug)]
 pub struct TraceToken {
-  serial: i64,
-  previous: i64,
-  from: Pid,
-  label: i64,
-  flags: i64,
+  pub(crate) serial: i64,
+  pub(crate) previous: i64,
+  pub(crate) from: Pid,
+  pub(crate) label: i64,
+  pub(crate) flags: i64,
 }
 
 #[derive(Debug)]
 pub enum ControlMessage {
   Send {
-    from: Pid,
+    /// The sender, which `SEND` messages do not carry.
+    from: Option<Pid>,
     to: Pid,
     trace_token: Option<TraceToken>,
   },
@@ -103,7 +213,8 @@ pub enum ControlMessage {
 #[derive(Debug)]
 pub enum Message {
   Send {
-    from: Pid,
+    /// The sender, which `SEND` messages do not carry.
+    from: Option<Pid>,
     to: Pid,
     trace_token: Option<TraceToken>,
     term: Term,
@@ -138,8 +249,64 @@ pub struct AtomCache {
   pub entries: collections::HashMap<AtomCacheKey, Atom>,
 }
 
+/// The atoms that the distribution header of a message refers to, in order. `ATOM_CACHE_REF` tags in
+/// the message are indices in this table.
+#[derive(Debug, Clone, Default)]
+pub struct AtomCacheReferences(pub Vec<Atom>);
+
+/// The messages that the peer sent in fragments and that have not been fully received yet, by
+/// sequence id.
+pub struct Fragments {
+  pub(crate) sequences: collections::HashMap<u64, FragmentSequence>,
+  /// The messages that were dropped before their last fragment, whose remaining fragments are
+  /// discarded.
+  pub(crate) dropped_sequences: collections::HashSet<u64>,
+  pub(crate) len: usize,
+  pub(crate) max_len: usize,
+}
+
+/// The fragments of a message that were received so far.
+pub(crate) struct FragmentSequence {
+  pub(crate) atom_references: AtomCacheReferences,
+  pub(crate) next_fragment_id: u64,
+  pub(crate) bytes: Vec<u8>,
+}
+
+/// Splits the messages that are sent to the peer into fragments.
+pub struct Fragmenter {
+  pub(crate) max_fragment_size: usize,
+  pub(crate) next_sequence_id: u64,
+  /// The packets that are left to send for each message being sent, in the order the messages take
+  /// turns.
+  pub(crate) pending: collections::VecDeque<collections::VecDeque<Vec<u8>>>,
+}
+
+/// Numbers the nodes of pids, ports and references like a runtime does to print them, as the first
+/// number of `<5.42.0>`. The local node is 0 and the other nodes are numbered from 1 in the order
+/// they are first printed. See `Term::display_with`.
+#[derive(Debug, Clone)]
+pub struct NodeNumbering {
+  pub(crate) local: Node,
+  pub(crate) others: cell::RefCell<Vec<Node>>,
+}
+
+/// Formats a term like its `Display` implementation, with the nodes of its pids, ports and
+/// references numbered. See `Term::display_with`.
+pub struct NumberedTerm<'term> {
+  pub(crate) term: &'term Term,
+  pub(crate) numbering: &'term NodeNumbering,
+}
+
+/// Formats a term like `io:format("~p")`, breaking it across lines. See `Term::pretty`.
+pub struct Pretty<'term> {
+  pub(crate) term: &'term Term,
+  pub(crate) width: usize,
+  pub(crate) numbering: Option<&'term NodeNumbering>,
+}
+
 pub struct TermViewBuffer<'term> {
   pub atoms: cursed_collections::AppendOnlyVec<&'term str>,
+  pub(crate) views: cursed_collections::AppendOnlyVec<Box<[TermView<'term>]>>,
 }
 
 #[derive(Debug)]
@@ -147,5 +314,17 @@ pub enum TermView<'term> {
   Nil,
   Atom(&'term str),
   Integer(i32),
+  BigInteger(&'term BigInteger),
   Float(f64),
+  Pid(&'term Pid),
+  Port(&'term Port),
+  Reference(&'term Reference),
+  Tuple(&'term [TermView<'term>]),
+  /// The elements of a non-empty list, followed by its tail, which is `Nil` for proper lists.
+  List(&'term [TermView<'term>], &'term TermView<'term>),
+  Binary(&'term [u8]),
+  BitBinary(&'term BitBinary),
+  Map(&'term Map),
+  Export(&'term Export),
+  Fun(&'term Fun),
 }
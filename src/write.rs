@@ -0,0 +1,31 @@
+use crate::err;
+use std::io;
+
+pub fn bytes<W: io::Write>(output: &mut W, value: &[u8]) -> err::Result<()> {
+  output.write_all(value)?;
+  Ok(())
+}
+
+pub fn be_u8<W: io::Write>(output: &mut W, value: u8) -> err::Result<()> {
+  bytes(output, &[value])
+}
+
+pub fn be_u16<W: io::Write>(output: &mut W, value: u16) -> err::Result<()> {
+  bytes(output, &value.to_be_bytes())
+}
+
+pub fn be_u32<W: io::Write>(output: &mut W, value: u32) -> err::Result<()> {
+  bytes(output, &value.to_be_bytes())
+}
+
+pub fn be_i32<W: io::Write>(output: &mut W, value: i32) -> err::Result<()> {
+  bytes(output, &value.to_be_bytes())
+}
+
+pub fn be_u64<W: io::Write>(output: &mut W, value: u64) -> err::Result<()> {
+  bytes(output, &value.to_be_bytes())
+}
+
+pub fn be_f64<W: io::Write>(output: &mut W, value: f64) -> err::Result<()> {
+  be_u64(output, value.to_bits())
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "derive")]
+
+use erlang_cnode::{Atom, ConversionPathSegment, ErrorKind, FromTerm, IntoTerm, List, Term, Tuple};
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, FromTerm, IntoTerm)]
+struct HttpRequest {
+  method: Atom,
+  path: String,
+  headers: HashMap<String, String>,
+  body: Option<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, FromTerm, IntoTerm)]
+#[term(record = "pt")]
+struct Point(i64, f64);
+
+#[derive(Debug, PartialEq, FromTerm, IntoTerm)]
+struct Empty;
+
+#[derive(Debug, PartialEq, FromTerm, IntoTerm)]
+struct Wrapper<T> {
+  inner: T,
+}
+
+fn atom(name: &str) -> Term {
+  Term::Atom(Atom::new(name).unwrap())
+}
+
+#[test]
+fn records_round_trip() {
+  let mut headers = HashMap::new();
+  headers.insert("accept".to_owned(), "*/*".to_owned());
+  let request = || HttpRequest {
+    method: Atom::new("get").unwrap(),
+    path: "/".to_owned(),
+    headers: headers.clone(),
+    body: Some(vec![1, 2]),
+  };
+  let term = request().into_term();
+  match &term {
+    Term::Tuple(Tuple(elements)) => {
+      assert_eq!(elements.len(), 5);
+      assert_eq!(elements[0], atom("http_request"));
+    }
+    term => panic!("{:?}", term),
+  }
+  assert_eq!(HttpRequest::from_term(&term).unwrap(), request());
+
+  let term = Point(1 << 40, 2.5).into_term();
+  match &term {
+    Term::Tuple(Tuple(elements)) => assert_eq!(elements[0], atom("pt")),
+    term => panic!("{:?}", term),
+  }
+  assert_eq!(Point::from_term(&term).unwrap(), Point(1 << 40, 2.5));
+
+  assert_eq!(
+    Empty.into_term(),
+    Tuple(vec![atom("empty")].into_boxed_slice()).into()
+  );
+  assert_eq!(Empty::from_term(&Empty.into_term()).unwrap(), Empty);
+
+  let wrapper = Wrapper { inner: (1u8, true) };
+  let term = wrapper.into_term();
+  assert_eq!(
+    Wrapper::<(u8, bool)>::from_term(&term).unwrap(),
+    Wrapper { inner: (1, true) }
+  );
+}
+
+#[test]
+fn record_errors_name_the_field() {
+  let term = Tuple(vec![atom("pt"), Term::Integer(1), atom("x")].into_boxed_slice()).into();
+  // Unnamed fields are numbered from 1.
+  match Point::from_term(&term).unwrap_err().0 {
+    ErrorKind::ConversionFailed(path, _, _) => assert_eq!(
+      path.0,
+      [ConversionPathSegment::RecordField {
+        record: "pt",
+        field: "2",
+      }]
+    ),
+    kind => panic!("{:?}", kind),
+  }
+
+  let term =
+    Tuple(vec![atom("point"), Term::Integer(1), Term::Float(1.0)].into_boxed_slice()).into();
+  assert!(Point::from_term(&term).is_err());
+  let term = List::new(vec![atom("pt"), Term::Integer(1), Term::Float(1.0)]).into();
+  assert!(Point::from_term(&term).is_err());
+}
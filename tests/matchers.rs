@@ -0,0 +1,54 @@
+use erlang_cnode::{atom, binary, int, list, pid, tuple};
+use erlang_cnode::{Atom, Binary, List, Node, Pid, Term, TermViewBuffer, Tuple};
+
+fn atom(name: &str) -> Term {
+  Term::Atom(Atom::new(name).unwrap())
+}
+
+#[test]
+fn nested_views_match() {
+  let pid = Pid::new(Node::new(Atom::new("a@b").unwrap(), 1), 5, 0);
+  let term = Tuple(
+    vec![
+      atom("reply"),
+      pid.into(),
+      List::improper(
+        vec![Binary(Box::new(*b"hi")).into(), Term::Integer(2)],
+        atom("more"),
+      )
+      .into(),
+    ]
+    .into_boxed_slice(),
+  )
+  .into();
+
+  let buffer = TermViewBuffer::new();
+  match buffer.view(&term) {
+    tuple![
+      atom!("reply"),
+      pid!(pid),
+      list![binary!(b"hi"), int!(n) | atom!(tail)]
+    ] => {
+      assert_eq!(pid.id(), 5);
+      assert_eq!(*n, 2);
+      assert_eq!(*tail, "more");
+    }
+    view => panic!("{:?}", view),
+  }
+}
+
+#[test]
+fn lists_match_by_shape() {
+  let buffer = TermViewBuffer::new();
+  let proper = List::new(vec![Term::Integer(1), Term::Integer(2)]).into();
+  let improper = List::improper(vec![Term::Integer(1)], Term::Integer(2)).into();
+  let nested = Tuple(vec![List::new(vec![atom("x")]).into()].into_boxed_slice()).into();
+
+  assert!(matches!(buffer.view(&Term::Nil), list![]));
+  assert!(matches!(buffer.view(&proper), list![int!(1), int!(2)]));
+  assert!(matches!(buffer.view(&proper), list![int!(1), ..]));
+  assert!(!matches!(buffer.view(&proper), list![int!(1)]));
+  assert!(matches!(buffer.view(&improper), list![int!(1) | int!(2)]));
+  assert!(!matches!(buffer.view(&improper), list![int!(1), ..]));
+  assert!(matches!(buffer.view(&nested), tuple![list![atom!("x")]]));
+}
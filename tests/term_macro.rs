@@ -0,0 +1,72 @@
+#![cfg(feature = "derive")]
+
+use erlang_cnode::{term, Atom, Binary, List, Map, Term, Tuple};
+
+fn atom(name: &str) -> Term {
+  Term::Atom(Atom::new(name).unwrap())
+}
+
+fn tuple(elements: Vec<Term>) -> Term {
+  Tuple(elements.into_boxed_slice()).into()
+}
+
+#[test]
+fn literals_build_terms() {
+  assert_eq!(term!(ok), atom("ok"));
+  assert_eq!(term!(type), atom("type"));
+  assert_eq!(term!(true), atom("true"));
+  assert_eq!(term!(-5), Term::Integer(-5));
+  assert_eq!(term!(3000000000), Term::from(3_000_000_000i64));
+  assert_eq!(term!(-1.5), Term::Float(-1.5));
+  assert_eq!(
+    term!("hi"),
+    List::new(vec![Term::Integer(104), Term::Integer(105)]).into()
+  );
+  assert_eq!(term!([]), Term::Nil);
+  assert_eq!(term!(<<>>), Binary(Box::new([])).into());
+  assert_eq!(term!({}), tuple(vec![]));
+  assert_eq!(term!(#{}), Map(Box::new([])).into());
+}
+
+#[test]
+#[allow(non_snake_case)]
+fn compound_terms_nest() {
+  let Value = 7u8;
+  let Bytes = vec![9u8];
+  let Tail = Atom::new("more").unwrap();
+
+  assert_eq!(
+    term!({ok, [1, 2 | Tail], <<"bin", 0, Bytes>>, [a | b]}),
+    tuple(vec![
+      atom("ok"),
+      List::improper(vec![Term::Integer(1), Term::Integer(2)], atom("more")).into(),
+      Binary(Box::new(*b"bin\0\x09")).into(),
+      List::improper(vec![atom("a")], atom("b")).into(),
+    ])
+  );
+
+  let Rest = vec![3, 4];
+  match term!([1, 2 | Rest]) {
+    Term::List(list) => {
+      assert!(list.is_proper());
+      assert_eq!(list.len(), 4);
+    }
+    term => panic!("{:?}", term),
+  }
+
+  assert_eq!(
+    term!(#{key => Value, "s" => (1 + 1), {nested} => [Value]}),
+    Map(
+      vec![
+        (atom("key"), Term::Integer(7)),
+        (List::new(vec![Term::Integer(115)]).into(), Term::Integer(2)),
+        (
+          tuple(vec![atom("nested")]),
+          List::new(vec![Term::Integer(7)]).into()
+        ),
+      ]
+      .into_boxed_slice()
+    )
+    .into()
+  );
+}